@@ -0,0 +1,54 @@
+use crate::board::Board;
+use crate::gamestate::Gamestate;
+use crate::move_list::MoveList;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::time::Instant;
+
+/// Depth of the benchmark perft runs.
+const DEPTH: u8 = 6;
+
+/// Seeds of the starting positions the benchmark is run on.
+const SEEDS: [u64; 3] = [1, 2, 3];
+
+/// Runs a perft benchmark of [`Board::legal_moves`] against the vector based
+/// [`Board::legal_moves_by_vectors`] and prints the speedup.
+pub fn run() {
+    let mut total_tables = 0f64;
+    let mut total_vectors = 0f64;
+
+    for seed in SEEDS {
+        let mut gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(seed));
+
+        let (nodes, tables) = time_perft(&mut gamestate, Board::legal_moves);
+        let (reference, vectors) = time_perft(&mut gamestate, Board::legal_moves_by_vectors);
+        assert_eq!(
+            nodes, reference,
+            "Move generators disagree on seed {}",
+            seed
+        );
+
+        println!(
+            "Seed {} | Depth {} | {} nodes | tables {:.3}s ({:.0} nps) | vectors {:.3}s ({:.0} nps)",
+            seed,
+            DEPTH,
+            nodes,
+            tables,
+            nodes as f64 / tables,
+            vectors,
+            nodes as f64 / vectors
+        );
+
+        total_tables += tables;
+        total_vectors += vectors;
+    }
+
+    println!("Speedup: {:.2}x", total_vectors / total_tables);
+}
+
+/// Runs perft with the given move generator and returns the node count and the seconds taken.
+fn time_perft(gamestate: &mut Gamestate, generate: fn(&Board) -> MoveList) -> (u64, f64) {
+    let start = Instant::now();
+    let nodes = gamestate.perft_with(DEPTH, generate);
+    (nodes, start.elapsed().as_secs_f64())
+}
@@ -46,7 +46,7 @@ macro_rules! mask_from_coords {
     };
 }
 
-#[derive(Copy)]
+#[derive(Copy, PartialEq, Eq)]
 pub struct Bitboard {
     pub bits: u64,
 }
@@ -252,9 +252,7 @@ impl Bitboard {
     /// //mask1 now has the value of mask2, and vice versa
     /// ```
     pub fn swap_with(&mut self, other: &mut Bitboard) {
-        self.bits ^= other.bits;
-        other.bits ^= self.bits;
-        self.bits ^= other.bits;
+        std::mem::swap(&mut self.bits, &mut other.bits);
     }
 
     /// Efficiently calculates the indexes of all bits that are set to
@@ -291,6 +289,27 @@ impl Bitboard {
         out
     }
 
+    /// Returns the number of bits that are set to 1.
+    pub const fn count(&self) -> u32 {
+        self.bits.count_ones()
+    }
+
+    /// Iterates over the indexes of all bits that are set to 1 in this bitboard, from the
+    /// least to the most significant. Unlike [`Bitboard::get_set_bits`], this uses a bit-scan
+    /// and doesn't allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mask = Bitboard::from_bits(0b10100001);
+    /// println!("{:?}", mask.iter().collect::<Vec<u8>>());
+    ///
+    /// //> [0, 5, 7]
+    /// ```
+    pub const fn iter(&self) -> SetBits {
+        SetBits { bits: self.bits }
+    }
+
     pub fn rotate90_clockwise(&self) -> Self {
         self.flip_vertical().flip_diagonal_a1_h8()
     }
@@ -303,7 +322,7 @@ impl Bitboard {
         let mut x = self.bits;
         x = ((x >> 8) & K1) | ((x & K1) << 8);
         x = ((x >> 16) & K2) | ((x & K2) << 16);
-        x = (x >> 32) | (x << 32);
+        x = x.rotate_left(32);
         Bitboard { bits: x }
     }
 
@@ -334,11 +353,29 @@ impl Bitboard {
         x = ((x >> 4) & H3) | ((x & H3) << 4);
         x = ((x >> 8) & K1) | ((x & K1) << 8);
         x = ((x >> 16) & K2) | ((x & K2) << 16);
-        x = (x >> 32) | (x << 32);
+        x = x.rotate_left(32);
         Bitboard { bits: x }
     }
 }
 
+/// Iterator over the set bits of a [`Bitboard`], see [`Bitboard::iter`].
+pub struct SetBits {
+    bits: u64,
+}
+
+impl Iterator for SetBits {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bits == 0 {
+            return None;
+        }
+        let pos = self.bits.trailing_zeros() as u8;
+        self.bits &= self.bits - 1;
+        Some(pos)
+    }
+}
+
 impl From<u64> for Bitboard {
     fn from(bits: u64) -> Self {
         Bitboard { bits }
@@ -355,7 +392,7 @@ impl From<Coords> for Bitboard {
 
 impl Clone for Bitboard {
     fn clone(&self) -> Self {
-        Bitboard { bits: self.bits }
+        *self
     }
 }
 
@@ -1,169 +1,352 @@
 use crate::bitboard::Bitboard;
 use crate::coords;
+use crate::error::GameError;
 use crate::game_move::Move;
+use crate::move_list::MoveList;
+use crate::piece::destinations;
 use crate::piece::pieces::*;
 use crate::piece::PieceType;
 use crate::position;
 use crate::team::Team;
 use crate::vec2::Vec2;
 use crate::xml_node::XmlNode;
+use crate::zobrist::ZOBRIST;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Copy, Clone)]
+/// The base lines light pieces have to reach to earn an amber, indexed by the moving team.
+/// Team one starts at `x = 0` and moves towards `x = 7`, team two the other way round.
+const SCORING_LINES: [Bitboard; 2] = [
+    Bitboard {
+        bits: 0x0101_0101_0101_0101,
+    },
+    Bitboard {
+        bits: 0x8080_8080_8080_8080,
+    },
+];
+
+/// The board in the server's coordinate system, with the pieces of both teams stored by
+/// owner and the team whose turn it is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Board {
-    pub enemy_pieces: Bitboard,
-    pub friendly_pieces: Bitboard,
+    pub team_one: Bitboard,
+    pub team_two: Bitboard,
     pub seesterne: Bitboard,
     pub muscheln: Bitboard,
     pub moewen: Bitboard,
     pub robben: Bitboard,
     pub double_stack: Bitboard,
+    pub side_to_move: Team,
+    /// Zobrist hash of the position, see [`Zobrist`](crate::zobrist::Zobrist). Kept up to
+    /// date by [`Board::apply`]; the amber keys are maintained by the
+    /// [`Gamestate`](crate::gamestate::Gamestate), which adds the key of the turn.
+    pub hash: u64,
 }
 
 impl Board {
     pub const fn new() -> Self {
         Board {
-            enemy_pieces: Bitboard::new(),
-            friendly_pieces: Bitboard::new(),
+            team_one: Bitboard::new(),
+            team_two: Bitboard::new(),
             seesterne: Bitboard::new(),
             muscheln: Bitboard::new(),
             moewen: Bitboard::new(),
             robben: Bitboard::new(),
             double_stack: Bitboard::new(),
+            side_to_move: Team::ONE,
+            hash: 0,
         }
     }
 
-    pub fn legal_moves(&self) -> Vec<Move> {
-        let moewen = self.moewen & self.friendly_pieces;
-        let robben = self.robben & self.friendly_pieces;
-        let muscheln = self.muscheln & self.friendly_pieces;
-        let seesterne = self.seesterne & self.friendly_pieces;
+    /// Returns the pieces owned by the given team.
+    pub const fn pieces(&self, team: Team) -> Bitboard {
+        match team {
+            Team::ONE => self.team_one,
+            Team::TWO => self.team_two,
+        }
+    }
 
-        let mut out = MOEWE.calculate_moves(moewen, self);
-        out.append(&mut ROBBE.calculate_moves(robben, self));
-        out.append(&mut MUSCHEL.calculate_moves(muscheln, self));
-        out.append(&mut SEESTERN.calculate_moves(seesterne, self));
+    fn pieces_mut(&mut self, team: Team) -> &mut Bitboard {
+        match team {
+            Team::ONE => &mut self.team_one,
+            Team::TWO => &mut self.team_two,
+        }
+    }
 
-        return out;
+    /// Returns the team owning the piece at the given position, if any.
+    pub const fn owner_at(&self, pos: u8) -> Option<Team> {
+        if self.team_one.get(pos) {
+            Some(Team::ONE)
+        } else if self.team_two.get(pos) {
+            Some(Team::TWO)
+        } else {
+            None
+        }
     }
 
+    /// Creates a starting position, with both teams' pieces shuffled along their base lines.
+    /// As on the server, the line-up of team two mirrors team one's through the centre.
+    pub fn random_start<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut line_up = [
+            PieceType::ROBBE,
+            PieceType::ROBBE,
+            PieceType::MUSCHEL,
+            PieceType::MUSCHEL,
+            PieceType::SEESTERN,
+            PieceType::SEESTERN,
+            PieceType::MOEWE,
+            PieceType::MOEWE,
+        ];
+        line_up.shuffle(rng);
+        Board::from_line_up(&line_up)
+    }
+
+    /// Creates the starting position with team one's pieces lined up from `y = 0` to
+    /// `y = 7`. Team two's line-up mirrors it through the centre.
+    pub fn from_line_up(line_up: &[PieceType; 8]) -> Self {
+        let mut board = Board::new();
+        for (y, piece) in line_up.iter().enumerate() {
+            let one = position!(0, y);
+            let two = position!(7, 7 - y);
+
+            board.team_one.set(one);
+            board.place(*piece, one, false);
+            board.team_two.set(two);
+            board.place(*piece, two, false);
+        }
+        board.hash = board.calculate_hash();
+        board
+    }
+
+    /// Calculates the Zobrist hash of the pieces and the side to move from scratch.
+    pub fn calculate_hash(&self) -> u64 {
+        let mut hash = match self.side_to_move {
+            Team::ONE => 0,
+            Team::TWO => ZOBRIST.side(),
+        };
+        for pos in (self.team_one | self.team_two).get_set_bits() {
+            hash ^= self.square_hash(pos);
+        }
+        hash
+    }
+
+    /// The Zobrist key of the piece standing at the position, or 0 for an empty square.
+    fn square_hash(&self, pos: u8) -> u64 {
+        match (self.piece_at(pos), self.owner_at(pos)) {
+            (Some(piece), Some(team)) => {
+                ZOBRIST.piece(piece, team, self.double_stack.get(pos), pos)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Generates the legal moves of the team whose turn it is, by looking up the
+    /// precomputed destinations of every piece.
+    pub fn legal_moves(&self) -> MoveList {
+        let team = self.side_to_move.index();
+        let own = self.pieces(self.side_to_move);
+        let opponent = self.pieces(self.side_to_move.next());
+        let mut out = MoveList::new();
+
+        for (piece, pieces) in [
+            (PieceType::MOEWE, self.moewen),
+            (PieceType::ROBBE, self.robben),
+            (PieceType::MUSCHEL, self.muscheln),
+            (PieceType::SEESTERN, self.seesterne),
+        ] {
+            let table = destinations::of(piece);
+
+            for origin in (pieces & own).iter() {
+                for target in (table[team][origin as usize] & !own).iter() {
+                    out.push(Move::new(origin, target, piece, opponent.get(target)));
+                }
+            }
+        }
+        out
+    }
+
+    /// Generates the legal moves of the team whose turn it is, with
+    /// [`Piece::calculate_moves`](crate::piece::Piece::calculate_moves).
+    pub fn legal_moves_by_vectors(&self) -> MoveList {
+        let own = self.pieces(self.side_to_move);
+        let moewen = self.moewen & own;
+        let robben = self.robben & own;
+        let muscheln = self.muscheln & own;
+        let seesterne = self.seesterne & own;
+
+        let mut out = MoveList::new();
+        MOEWE.calculate_moves(moewen, self, &mut out);
+        ROBBE.calculate_moves(robben, self, &mut out);
+        MUSCHEL.calculate_moves(muscheln, self, &mut out);
+        SEESTERN.calculate_moves(seesterne, self, &mut out);
+
+        out
+    }
+
+    /// Finds the legal move of the team whose turn it is between the two positions.
+    pub fn move_between(&self, from: u8, to: u8) -> Option<Move> {
+        self.legal_moves()
+            .iter()
+            .find(|r#move| r#move.from() == from && r#move.to() == to)
+            .copied()
+    }
+
+    /// Applies a legal move of the team whose turn it is, returns the number of ambers it
+    /// earned and passes the turn on to the opponent.
+    ///
+    /// A move earns an amber if the resulting tower would consist of three or more pieces,
+    /// and another one if a light piece (Möwe, Seestern, Herzmuschel) reaches the opponent's
+    /// base line. As on the server, a scoring piece is taken off the board.
     pub fn apply(&mut self, r#move: &Move) -> u8 {
         //We know that the move is legal, now apply it to the board
+        let team = self.side_to_move;
+        let opponent = team.next();
 
-        let origin = position!(r#move.origin.x as u8, r#move.origin.y as u8) as u8;
+        let origin = r#move.from();
+        let pos = r#move.to();
 
-        //Clear origin position of data
-        match r#move.piece {
-            PieceType::ROBBE => self.robben.clear(origin),
-            PieceType::MUSCHEL => self.muscheln.clear(origin),
-            PieceType::SEESTERN => self.seesterne.clear(origin),
-            PieceType::MOEWE => self.moewen.clear(origin),
-        }
+        //Count the pieces making up the moving stack
+        let mut count = if self.double_stack.get(origin) { 2 } else { 1 };
 
-        self.friendly_pieces.clear(origin);
-        self.double_stack.clear(origin);
+        //Clear origin position of data
+        self.hash ^= self.square_hash(origin);
+        self.clear_square(origin);
 
         ////////////////////////////////////////////////////////
 
-        let set_move = r#move.origin + r#move.vector;
+        //If there is an opposing piece at the position, it gets captured and
+        //its pieces are added to our stack.
+        if self.pieces(opponent).get(pos) {
+            count += if self.double_stack.get(pos) { 2 } else { 1 };
+            self.hash ^= self.square_hash(pos);
+            self.clear_square(pos);
+        }
+
+        //Ambers to increase the score by
+        let mut ambers = 0u8;
 
-        let pos = position!(set_move.x as u8, set_move.y as u8);
+        if count > 2 {
+            ambers += 1;
+        }
+        if r#move.piece().is_light() && SCORING_LINES[team.index()].get(pos) {
+            ambers += 1;
+        }
 
-        //Points to increase game by
-        let mut points = 0u8;
+        //A piece that scored gets removed from the board, so we only place
+        //it if it didn't earn anything.
+        if ambers == 0 {
+            self.pieces_mut(team).set(pos);
+            self.place(r#move.piece(), pos, count == 2);
+            self.hash ^= self.square_hash(pos);
+        }
 
-        //If the piece is stacked double, increase the count and remove
-        //it from the registers and remove any enemy that was there. Since
-        //the piece gets removed from the board, we don't need to add it.
-        if self.double_stack.get(pos) {
-            points = 1;
-            self.double_stack.clear(pos);
-            self.enemy_pieces.clear(pos);
-        } else {
-            //If the piece is not stacked double, we check whether there
-            //even is a piece at the position. If there is a piece at the
-            //position we need to remove it,and replace it with our own
-            if self.enemy_pieces.get(pos) {
-                self.double_stack.set(pos);
-
-                //Remove the enemy piece
-                self.enemy_pieces.clear(pos);
-
-                //Remove the enemy piece from the registers
-                self.seesterne.clear(pos);
-                self.muscheln.clear(pos);
-                self.moewen.clear(pos);
-                self.robben.clear(pos);
-            }
+        self.side_to_move = opponent;
+        self.hash ^= ZOBRIST.side();
+        ambers
+    }
 
-            //Board at given position is free - we can place our piece
-            self.friendly_pieces.set(pos);
+    /// The number of ambers a legal move of the team to move would earn, without applying it.
+    pub fn ambers_of(&self, r#move: &Move) -> u8 {
+        let pos = r#move.to();
+        let mut ambers = 0;
 
-            //Place the piece
-            match r#move.piece {
-                PieceType::ROBBE => self.robben.set(pos),
-                PieceType::MUSCHEL => self.muscheln.set(pos),
-                PieceType::SEESTERN => self.seesterne.set(pos),
-                PieceType::MOEWE => self.moewen.set(pos),
-            }
+        if r#move.is_capture()
+            && (self.double_stack.get(r#move.from()) || self.double_stack.get(pos))
+        {
+            ambers += 1;
+        }
+        if r#move.piece().is_light() && SCORING_LINES[self.side_to_move.index()].get(pos) {
+            ambers += 1;
         }
-        return points;
+        ambers
     }
 
-    ///Apply anonymous move. Maybe create an own struct?
-    pub fn apply_anonymous(&mut self, origin_pos: u8, result_pos: u8) -> u8 {
-        let origin = coords!(origin_pos);
-        let result = coords!(result_pos);
+    /// Applies a legal move like [`Board::apply`], but records everything needed to take
+    /// it back with [`Board::unmake_move`].
+    pub fn make_move(&mut self, r#move: &Move) -> Undo {
+        let origin = r#move.from();
+        let pos = r#move.to();
+
+        let captured = self
+            .piece_at(pos)
+            .map(|piece| (piece, self.double_stack.get(pos)));
+
+        let moved_stacked = self.double_stack.get(origin);
+        let hash = self.hash;
+        let ambers = self.apply(r#move);
+
+        Undo {
+            r#move: *r#move,
+            moved_stacked,
+            captured,
+            ambers,
+            hash,
+        }
+    }
 
-        let vector = result - origin;
-        let r#move = Move::new(
-            origin,
-            vector,
-            self.piece_at(origin_pos).expect("What the fuck"),
-        );
+    /// Takes back a move made with [`Board::make_move`], restoring the board exactly.
+    pub fn unmake_move(&mut self, undo: Undo) {
+        let team = self.side_to_move.next();
+        let opponent = self.side_to_move;
 
-        return self.apply(&r#move);
-    }
+        let origin = undo.r#move.from();
+        let pos = undo.r#move.to();
+
+        //A piece that scored was never placed, so there is only something to
+        //remove if the move didn't earn any ambers.
+        if undo.ambers == 0 {
+            self.clear_square(pos);
+        }
 
-    pub fn rotate90_clockwise(&mut self) {
-        self.friendly_pieces = self.friendly_pieces.rotate90_clockwise();
-        self.enemy_pieces = self.enemy_pieces.rotate90_clockwise();
-        self.double_stack = self.double_stack.rotate90_clockwise();
-        self.robben = self.robben.rotate90_clockwise();
-        self.seesterne = self.seesterne.rotate90_clockwise();
-        self.moewen = self.moewen.rotate90_clockwise();
-        self.muscheln = self.muscheln.rotate90_clockwise();
+        if let Some((piece, stacked)) = undo.captured {
+            self.pieces_mut(opponent).set(pos);
+            self.place(piece, pos, stacked);
+        }
+
+        self.pieces_mut(team).set(origin);
+        self.place(undo.r#move.piece(), origin, undo.moved_stacked);
+
+        self.side_to_move = team;
+        self.hash = undo.hash;
     }
 
-    pub fn rotate90_anti_clockwise(&mut self) {
-        self.friendly_pieces = self.friendly_pieces.rotate90_anti_clockwise();
-        self.enemy_pieces = self.enemy_pieces.rotate90_anti_clockwise();
-        self.double_stack = self.double_stack.rotate90_anti_clockwise();
-        self.robben = self.robben.rotate90_anti_clockwise();
-        self.seesterne = self.seesterne.rotate90_anti_clockwise();
-        self.moewen = self.moewen.rotate90_anti_clockwise();
-        self.muscheln = self.muscheln.rotate90_anti_clockwise();
+    /// Removes whatever piece is standing at the position from all registers.
+    fn clear_square(&mut self, pos: u8) {
+        self.team_one.clear(pos);
+        self.team_two.clear(pos);
+        self.double_stack.clear(pos);
+        self.seesterne.clear(pos);
+        self.muscheln.clear(pos);
+        self.moewen.clear(pos);
+        self.robben.clear(pos);
     }
 
-    pub fn rotate180(&mut self) {
-        self.friendly_pieces = self.friendly_pieces.rotate180();
-        self.enemy_pieces = self.enemy_pieces.rotate180();
-        self.double_stack = self.double_stack.rotate180();
-        self.robben = self.robben.rotate180();
-        self.seesterne = self.seesterne.rotate180();
-        self.moewen = self.moewen.rotate180();
-        self.muscheln = self.muscheln.rotate180();
+    /// Sets the piece registers at the position. The owner has to be set separately.
+    fn place(&mut self, piece: PieceType, pos: u8, stacked: bool) {
+        match piece {
+            PieceType::ROBBE => self.robben.set(pos),
+            PieceType::MUSCHEL => self.muscheln.set(pos),
+            PieceType::SEESTERN => self.seesterne.set(pos),
+            PieceType::MOEWE => self.moewen.set(pos),
+        }
+        if stacked {
+            self.double_stack.set(pos);
+        }
     }
 
-    pub fn flip_horizontal(&mut self) {
-        self.friendly_pieces = self.friendly_pieces.flip_horizontal();
-        self.enemy_pieces = self.enemy_pieces.flip_horizontal();
-        self.double_stack = self.double_stack.flip_horizontal();
-        self.robben = self.robben.flip_horizontal();
-        self.seesterne = self.seesterne.flip_horizontal();
-        self.moewen = self.moewen.flip_horizontal();
-        self.muscheln = self.muscheln.flip_horizontal();
+    ///Apply anonymous move. Maybe create an own struct?
+    pub fn apply_anonymous(&mut self, origin_pos: u8, result_pos: u8) -> u8 {
+        let r#move = Move::new(
+            origin_pos,
+            result_pos,
+            self.piece_at(origin_pos).expect("What the fuck"),
+            self.pieces(self.side_to_move.next()).get(result_pos),
+        );
+
+        self.apply(&r#move)
     }
 
     pub fn piece_at(&self, pos: u8) -> Option<PieceType> {
@@ -180,24 +363,38 @@ impl Board {
     }
 }
 
+/// Record of a move made with [`Board::make_move`].
+#[derive(Debug, Copy, Clone)]
+pub struct Undo {
+    pub r#move: Move,
+    /// Whether the moving piece was a tower before the move
+    moved_stacked: bool,
+    /// The captured enemy piece, and whether it was a tower
+    captured: Option<(PieceType, bool)>,
+    /// The ambers earned by the move
+    pub ambers: u8,
+    /// The hash before the move
+    hash: u64,
+}
+
 impl Display for Board {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let mut out: String = String::from("╔══════════════════════════╗\n║  ");
 
+        //Pieces of team one are printed in upper case, those of team two in lower case
         for i in (0..64).rev() {
             let plot = match self.piece_at(i) {
-                None => "-",
-                Some(piece) => match piece {
-                    PieceType::ROBBE => "R",
-                    PieceType::MUSCHEL => "H",
-                    PieceType::SEESTERN => "S",
-                    PieceType::MOEWE => "M",
-                },
+                None => '-',
+                Some(piece) => piece.symbol(),
             };
 
-            out.push_str(plot);
+            if self.team_two.get(i) {
+                out.push(plot.to_ascii_lowercase());
+            } else {
+                out.push(plot);
+            }
             if self.double_stack.get(i) {
-                out.push_str("*")
+                out.push('*')
             } else {
                 out.push(' ');
             }
@@ -217,83 +414,290 @@ impl Display for Board {
 }
 
 ///From board node
-impl From<&XmlNode> for Board {
-    fn from(node: &XmlNode) -> Self {
+impl TryFrom<&XmlNode> for Board {
+    type Error = GameError;
+
+    fn try_from(node: &XmlNode) -> Result<Self, Self::Error> {
         let mut board = Board::new();
-        let pieces = node.child("pieces").unwrap();
+        let pieces = node.required_child("pieces")?;
 
         for entry in &pieces.children {
-            let coordinates = entry.child("coordinates").unwrap();
-            let x = coordinates
-                .attributes
-                .get("x")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<u8>()
-                .expect("Failed to parse coordinates while deserializing");
-
-            let y = coordinates
-                .attributes
-                .get("y")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<u8>()
-                .expect("Failed to parse coordinates while deserializing");
-
-            let piece_node = entry.child("piece").unwrap();
-
-            let piece_type = PieceType::from(
-                piece_node
-                    .attributes
-                    .get("type")
-                    .unwrap()
-                    .get(0)
-                    .expect("Failed to match piece"),
-            );
+            let coordinates = entry.required_child("coordinates")?;
+            let x = parse_coordinate(coordinates, "x")?;
+            let y = parse_coordinate(coordinates, "y")?;
 
-            let piece_team = piece_node
-                .attributes
-                .get("team")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<Team>()
-                .expect("Failed to associate Team while deserializing");
-
-            let stacked = match piece_node
-                .attributes
-                .get("count")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<u8>()
-            {
-                Ok(2) => true,
-                _ => false,
-            };
+            let piece_node = entry.required_child("piece")?;
+            let piece_type = piece_node.attribute("type")?.parse::<PieceType>()?;
+            let piece_team = piece_node.attribute("team")?.parse::<Team>()?;
+            let stacked = piece_node.parse_attribute::<u8>("count")? == 2;
 
+            //Server coordinates are used as they are
             let pos = position!(x, y);
 
-            //Presuming we are player 1
-            //If the piece team is ONE, then the piece is friendly. Else its the enemies piece.
-            match piece_team {
-                Team::ONE => board.friendly_pieces.set(pos),
-                Team::TWO => board.enemy_pieces.set(pos),
-            }
+            board.pieces_mut(piece_team).set(pos);
+            board.place(piece_type, pos, stacked);
+        }
+        board.hash = board.calculate_hash();
+        Ok(board)
+    }
+}
+
+/// Parses a coordinate attribute, which has to lie on the board.
+pub fn parse_coordinate(node: &XmlNode, name: &str) -> Result<u8, GameError> {
+    match node.parse_attribute::<u8>(name)? {
+        coordinate @ 0..=7 => Ok(coordinate),
+        coordinate => Err(GameError::InvalidValue {
+            node: node.name.clone(),
+            name: name.to_string(),
+            value: coordinate.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamestate::Gamestate;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Puts a piece of the team on the board.
+    fn put(board: &mut Board, team: Team, piece: PieceType, x: u8, y: u8, stacked: bool) {
+        let pos = position!(x, y);
+        board.pieces_mut(team).set(pos);
+        board.place(piece, pos, stacked);
+    }
+
+    /// Moves the piece at (x, y) by the vector and returns the state after it and the
+    /// ambers earned.
+    fn play(mut gamestate: Gamestate, x: i8, y: i8, vector: Vec2) -> (Gamestate, u8) {
+        let (from, to) = (position!(x, y), position!(x + vector.x, y + vector.y));
+        let piece = gamestate.board.piece_at(from).unwrap();
+        let capture = gamestate.board.owner_at(to) == Some(gamestate.current_team().next());
+        let ambers = gamestate.apply(&Move::new(from, to, piece, capture));
+        (gamestate, ambers)
+    }
+
+    /// A piece of team one next to the base line of team two, and a Robbe of team two.
+    fn before_base_line(piece: PieceType) -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        put(&mut gamestate.board, Team::ONE, piece, 6, 3, false);
+        put(
+            &mut gamestate.board,
+            Team::TWO,
+            PieceType::ROBBE,
+            7,
+            0,
+            false,
+        );
+        gamestate
+    }
 
-            match piece_type {
-                PieceType::ROBBE => board.robben.set(pos),
-                PieceType::MUSCHEL => board.muscheln.set(pos),
-                PieceType::SEESTERN => board.seesterne.set(pos),
-                PieceType::MOEWE => board.moewen.set(pos),
+    #[test]
+    fn light_pieces_score_on_the_base_line() {
+        for (piece, vector) in [
+            (PieceType::MOEWE, Vec2::new(1, 0)),
+            (PieceType::SEESTERN, Vec2::new(1, 0)),
+            (PieceType::MUSCHEL, Vec2::new(1, 1)),
+        ] {
+            let (gamestate, ambers) = play(before_base_line(piece), 6, 3, vector);
+            assert_eq!(ambers, 1, "{}", piece);
+            //The scoring piece is taken off the board
+            assert_eq!(gamestate.board.team_one.bits, 0, "{}", piece);
+            assert_eq!(gamestate.points.get_left(), 1);
+        }
+
+        let mut gamestate = Gamestate::new();
+        put(
+            &mut gamestate.board,
+            Team::TWO,
+            PieceType::MOEWE,
+            1,
+            3,
+            false,
+        );
+        put(
+            &mut gamestate.board,
+            Team::ONE,
+            PieceType::ROBBE,
+            0,
+            0,
+            false,
+        );
+        gamestate.board.side_to_move = Team::TWO;
+        let (gamestate, ambers) = play(gamestate, 1, 3, Vec2::new(-1, 0));
+        assert_eq!(ambers, 1);
+        assert_eq!(gamestate.board.team_two.bits, 0);
+        assert_eq!(gamestate.points.get_right(), 1);
+        assert_eq!(gamestate.points.get_left(), 0);
+    }
+
+    #[test]
+    fn robbe_reaches_the_base_line_without_scoring() {
+        let mut gamestate = Gamestate::new();
+        put(
+            &mut gamestate.board,
+            Team::ONE,
+            PieceType::ROBBE,
+            5,
+            3,
+            false,
+        );
+        put(
+            &mut gamestate.board,
+            Team::TWO,
+            PieceType::ROBBE,
+            7,
+            0,
+            false,
+        );
+        let (gamestate, ambers) = play(gamestate, 5, 3, Vec2::new(2, 1));
+        assert_eq!(ambers, 0);
+        assert_eq!(
+            gamestate.board.piece_at(position!(7, 4)),
+            Some(PieceType::ROBBE)
+        );
+        assert_eq!(gamestate.board.owner_at(position!(7, 4)), Some(Team::ONE));
+        assert_eq!(gamestate.points.get_left(), 0);
+    }
+
+    #[test]
+    fn tower_captures() {
+        let capture = |one_stacked: bool, two_stacked: bool| {
+            let mut gamestate = Gamestate::new();
+            put(
+                &mut gamestate.board,
+                Team::ONE,
+                PieceType::MOEWE,
+                3,
+                3,
+                one_stacked,
+            );
+            put(
+                &mut gamestate.board,
+                Team::TWO,
+                PieceType::MOEWE,
+                4,
+                3,
+                two_stacked,
+            );
+            put(
+                &mut gamestate.board,
+                Team::TWO,
+                PieceType::ROBBE,
+                7,
+                0,
+                false,
+            );
+            play(gamestate, 3, 3, Vec2::new(1, 0))
+        };
+        let pos = position!(4, 3);
+
+        //Two single pieces make a tower
+        let (gamestate, ambers) = capture(false, false);
+        assert_eq!(ambers, 0);
+        assert!(gamestate.board.double_stack.get(pos));
+        assert_eq!(gamestate.board.owner_at(pos), Some(Team::ONE));
+        assert_eq!(gamestate.board.team_two.get_set_bits().len(), 1);
+
+        //A tower capturing or being captured makes three pieces, which scores and leaves
+        //the square empty
+        for (one_stacked, two_stacked) in [(true, false), (false, true)] {
+            let (gamestate, ambers) = capture(one_stacked, two_stacked);
+            assert_eq!(ambers, 1);
+            assert_eq!(gamestate.board.piece_at(pos), None);
+            assert_eq!(gamestate.board.team_one.bits, 0);
+            assert_eq!(gamestate.board.double_stack.bits, 0);
+            assert_eq!(gamestate.points.get_left(), 1);
+        }
+    }
+
+    /// Mirrors a position along the middle between the base lines.
+    fn mirror(pos: u8) -> u8 {
+        let coords = Vec2::from_pos(pos);
+        position!(7 - coords.x, coords.y)
+    }
+
+    /// The board mirrored along the middle between the base lines, with the teams swapped.
+    fn mirrored(board: &Board) -> Board {
+        let mut out = Board::new();
+        for pos in 0..64u8 {
+            if let (Some(piece), Some(team)) = (board.piece_at(pos), board.owner_at(pos)) {
+                out.pieces_mut(team.next()).set(mirror(pos));
+                out.place(piece, mirror(pos), board.double_stack.get(pos));
             }
+        }
+        out.side_to_move = board.side_to_move.next();
+        out.hash = out.calculate_hash();
+        out
+    }
 
-            if stacked {
-                board.double_stack.set(pos);
+    #[test]
+    fn team_two_moves_mirror_team_one() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..20 {
+            for gamestate in Gamestate::random_game(&mut rng) {
+                let legal = gamestate.board.legal_moves();
+                let mirror_board = mirrored(&gamestate.board);
+                let mirror_legal = mirror_board.legal_moves();
+                assert_eq!(legal.len(), mirror_legal.len(), "\n{}", gamestate);
+
+                for r#move in legal.iter() {
+                    let mirrored_move = mirror_legal
+                        .iter()
+                        .find(|other| {
+                            other.from() == mirror(r#move.from())
+                                && other.to() == mirror(r#move.to())
+                        })
+                        .unwrap_or_else(|| panic!("No mirror of {} in\n{}", r#move, mirror_board));
+                    assert_eq!(mirrored_move.piece(), r#move.piece());
+                    assert_eq!(mirrored_move.is_capture(), r#move.is_capture());
+
+                    let mut after = gamestate.board;
+                    let mut mirror_after = mirror_board;
+                    assert_eq!(after.apply(r#move), mirror_after.apply(mirrored_move));
+                    assert_eq!(mirrored(&after), mirror_after);
+                }
             }
         }
-        board
+    }
+
+    fn board_with_piece(coordinates: &str, piece: &str) -> Result<Board, GameError> {
+        let xml = format!(
+            "<board><pieces><entry><coordinates {}/><piece {}/></entry></pieces></board>",
+            coordinates, piece
+        );
+        Board::try_from(&XmlNode::read_document(xml.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn rejects_malformed_pieces() {
+        let at = "x=\"0\" y=\"3\"";
+        let board = board_with_piece(at, "type=\"Robbe\" team=\"TWO\" count=\"2\"").unwrap();
+        assert_eq!(board.owner_at(position!(0, 3)), Some(Team::TWO));
+        assert!(board.double_stack.get(position!(0, 3)));
+
+        assert!(matches!(
+            board_with_piece(at, "type=\"Queen\" team=\"ONE\" count=\"1\""),
+            Err(GameError::UnknownPiece(piece)) if piece == "Queen"
+        ));
+        assert!(matches!(
+            board_with_piece(at, "type=\"Moewe\" team=\"RED\" count=\"1\""),
+            Err(GameError::UnknownTeam(team)) if team == "RED"
+        ));
+        assert!(matches!(
+            board_with_piece(at, "type=\"Moewe\" team=\"ONE\""),
+            Err(GameError::MissingAttribute { node, attribute })
+                if node == "piece" && attribute == "count"
+        ));
+        assert!(matches!(
+            board_with_piece("x=\"8\" y=\"3\"", "type=\"Moewe\" team=\"ONE\" count=\"1\""),
+            Err(GameError::InvalidValue { value, .. }) if value == "8"
+        ));
+        assert!(matches!(
+            Board::try_from(&XmlNode::read_document("<board/>".as_bytes()).unwrap()),
+            Err(GameError::MissingChild { child, .. }) if child == "pieces"
+        ));
     }
 }
@@ -0,0 +1,248 @@
+use crate::eval::EvalParams;
+use crate::game::Join;
+use crate::search::MAX_TT_BITS;
+use crate::strategy::{StrategyKind, StrategyOptions};
+use crate::time::TimeManager;
+use log::LevelFilter;
+use std::process;
+use std::slice;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage: client [options]
+       client bench
+       client perft <depth> <position> [moves...]
+       client server [--port <port>] [--games <n>] [--seed <n>]
+       client match <engine> <engine> [--games <n>] [--threads <n>] [--seed <n>]
+       client sprt <engine> <engine> [--elo0 <elo>] [--elo1 <elo>] [--output <file>] ...
+       client replay <file> [--analyze] [--engine <engine>] [--team <team>]
+       client tune generate <file> [--games <n>] [--engine <engine>] [--seed <n>]
+       client tune <dataset> [--output <file>] [--epochs <n>] [--rate <r>] [--params <file>]
+
+Options:
+  -h, --host <host>                Host of the game server [default: localhost]
+  -p, --port <port>                Port of the game server [default: 13050]
+  -r, --reservation <code>         Join the prepared game with this reservation code
+      --room <id>                  Join the open room with this id
+      --log-level <level>          off, error, warn, info, debug or trace [default: info]
+      --strategy <strategy>        random, greedy, alphabeta or mcts [default: alphabeta]
+      --move-limit <ms>            Time the server allows for a move [default: 2000]
+      --margin <ms>                Time kept back for the network [default: 300]
+      --tt-bits <bits>             Transposition table size as a power of two, 1 to 26 [default: 20]
+      --playout <policy>           MCTS playouts, random or light [default: light]
+      --eval <file>                Load the evaluation weights from the file
+      --no-ponder                  Don't think during the opponent's turn
+      --replay <dir>               Record the game to a replay file in the directory
+      --help                       Print this message";
+
+/// The options of a game client, as given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub host: String,
+    pub port: u16,
+    pub reservation: Option<String>,
+    pub room: Option<String>,
+    pub log_level: LevelFilter,
+    pub strategy: StrategyKind,
+    pub time: TimeManager,
+    pub engine: StrategyOptions,
+    pub ponder: bool,
+    pub replay: Option<String>,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            host: String::from("localhost"),
+            port: 13050,
+            reservation: None,
+            room: None,
+            log_level: LevelFilter::Info,
+            strategy: StrategyKind::AlphaBeta,
+            time: TimeManager::default(),
+            engine: StrategyOptions::default(),
+            ponder: true,
+            replay: None,
+            help: false,
+        }
+    }
+}
+
+impl Options {
+    /// Parses the arguments following the program name, as read by [`Flags`].
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut flags = Flags::new(args);
+
+        while let Some(flag) = flags.next_flag() {
+            match flag {
+                "-h" | "--host" => options.host = flags.value()?.to_string(),
+                "-p" | "--port" => options.port = flags.parse()?,
+                "-r" | "--reservation" => options.reservation = Some(flags.value()?.to_string()),
+                "--room" => options.room = Some(flags.value()?.to_string()),
+                "--log-level" => options.log_level = flags.parse()?,
+                "--strategy" => options.strategy = flags.parse()?,
+                "--move-limit" => options.time.limit = Duration::from_millis(flags.parse()?),
+                "--margin" => options.time.margin = Duration::from_millis(flags.parse()?),
+                "--tt-bits" => match flags.parse()? {
+                    bits @ 1..=MAX_TT_BITS => options.engine.tt_bits = bits,
+                    bits => {
+                        return Err(format!(
+                            "'--tt-bits' must be 1 to {}, got {}",
+                            MAX_TT_BITS, bits
+                        ))
+                    }
+                },
+                "--playout" => options.engine.playout = flags.parse()?,
+                "--eval" => options.engine.params = EvalParams::load(flags.value()?)?,
+                "--no-ponder" => options.ponder = false,
+                "--replay" => options.replay = Some(flags.value()?.to_string()),
+                "--help" => options.help = true,
+                flag => return Err(format!("Unknown option '{}'", flag)),
+            }
+        }
+
+        if options.reservation.is_some() && options.room.is_some() {
+            return Err(String::from(
+                "A reservation and a room can't be given together",
+            ));
+        }
+        Ok(options)
+    }
+
+    /// The address of the game server.
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// How to join a game: with the reservation code or into the room if given, otherwise
+    /// any open game.
+    pub fn join(&self) -> Join<'_> {
+        match (&self.reservation, &self.room) {
+            (Some(reservation), _) => Join::PREPARED(reservation),
+            (None, Some(room)) => Join::ROOM(room),
+            (None, None) => Join::ANY,
+        }
+    }
+}
+
+/// Reads command-line options one at a time, for the client and the developer commands
+/// alike. Values may be given as the next argument or joined by `=`, like `--port=13050`.
+pub(crate) struct Flags<'a> {
+    args: slice::Iter<'a, String>,
+    flag: &'a str,
+    /// A value joined to the current flag by `=`
+    inline: Option<&'a str>,
+}
+
+impl<'a> Flags<'a> {
+    pub(crate) fn new(args: &'a [String]) -> Self {
+        Flags {
+            args: args.iter(),
+            flag: "",
+            inline: None,
+        }
+    }
+
+    /// The next flag, or `None` once every argument is read.
+    pub(crate) fn next_flag(&mut self) -> Option<&'a str> {
+        let arg = self.args.next()?;
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+        self.flag = flag;
+        self.inline = inline;
+        Some(flag)
+    }
+
+    /// The value of the current flag.
+    pub(crate) fn value(&mut self) -> Result<&'a str, String> {
+        self.inline
+            .take()
+            .or_else(|| self.args.next().map(String::as_str))
+            .ok_or_else(|| format!("Missing value for '{}'", self.flag))
+    }
+
+    /// The value of the current flag, parsed as in [`parse_value`].
+    pub(crate) fn parse<T: FromStr>(&mut self) -> Result<T, String> {
+        let value = self.value()?;
+        parse_value(self.flag, value)
+    }
+}
+
+pub(crate) fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("Invalid value '{}' for '{}'", value, flag))
+}
+
+/// Reports an invalid command line together with the usage and exits with status 2.
+pub(crate) fn exit_with_usage(err: &str, usage: &str) -> ! {
+    eprintln!("{}\n\n{}", err, usage);
+    process::exit(2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn contest_arguments() {
+        let options = parse("--host 10.0.0.1 --port=13051 -r abc-123").unwrap();
+        assert_eq!(options.address(), "10.0.0.1:13051");
+        assert!(matches!(options.join(), Join::PREPARED("abc-123")));
+
+        assert!(matches!(parse("").unwrap().join(), Join::ANY));
+        assert_eq!(
+            parse("--strategy mcts").unwrap().strategy,
+            StrategyKind::Mcts
+        );
+        assert!(matches!(
+            parse("--room 42").unwrap().join(),
+            Join::ROOM("42")
+        ));
+    }
+
+    #[test]
+    fn reads_flags() {
+        let args: Vec<String> = "--games=4 --output out.json --seed"
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        let mut flags = Flags::new(&args);
+
+        assert_eq!(flags.next_flag(), Some("--games"));
+        assert_eq!(flags.parse::<u32>(), Ok(4));
+        assert_eq!(flags.next_flag(), Some("--output"));
+        assert_eq!(flags.value(), Ok("out.json"));
+        assert_eq!(flags.next_flag(), Some("--seed"));
+        assert_eq!(
+            flags.parse::<u64>(),
+            Err(String::from("Missing value for '--seed'"))
+        );
+        assert_eq!(flags.next_flag(), None);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(parse("--port").is_err());
+        assert!(parse("--port 70000").is_err());
+        assert!(parse("--log-level loud").is_err());
+        assert!(parse("--strategy minimax").is_err());
+        assert!(parse("--playout heavy").is_err());
+        assert!(parse("--tt-bits 0").is_err());
+        assert!(parse("--tt-bits 30").is_err());
+        assert_eq!(parse("--tt-bits 26").unwrap().engine.tt_bits, 26);
+        assert!(parse("--reservation a --room b").is_err());
+        assert!(parse("--unknown").is_err());
+        assert!(parse("--move-time 1500").is_err());
+    }
+}
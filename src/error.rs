@@ -0,0 +1,77 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// Everything that can go wrong while talking to the server.
+#[derive(Debug)]
+pub enum GameError {
+    /// Reading from or writing to the connection failed
+    Io(std::io::Error),
+    /// The received data isn't well-formed XML
+    Xml(xml::reader::Error),
+    /// The stream ended before a complete message was received
+    UnexpectedEof,
+    /// A node lacks an attribute the protocol requires
+    MissingAttribute { node: String, attribute: String },
+    /// A node lacks a child node the protocol requires
+    MissingChild { node: String, child: String },
+    /// An attribute or node holds a value that can't be parsed
+    InvalidValue {
+        node: String,
+        name: String,
+        value: String,
+    },
+    /// A piece type the client doesn't know
+    UnknownPiece(String),
+    /// A team the client doesn't know
+    UnknownTeam(String),
+    /// A message arrived that isn't allowed at this point of the game
+    Protocol(String),
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::Io(err) => write!(f, "I/O error: {}", err),
+            GameError::Xml(err) => write!(f, "Malformed XML: {}", err),
+            GameError::UnexpectedEof => write!(f, "Stream ended unexpectedly"),
+            GameError::MissingAttribute { node, attribute } => {
+                write!(f, "Node '{}' has no attribute '{}'", node, attribute)
+            }
+            GameError::MissingChild { node, child } => {
+                write!(f, "Node '{}' has no child '{}'", node, child)
+            }
+            GameError::InvalidValue { node, name, value } => {
+                write!(
+                    f,
+                    "Invalid value '{}' for '{}' in node '{}'",
+                    value, name, node
+                )
+            }
+            GameError::UnknownPiece(piece) => write!(f, "No piece of type '{}'", piece),
+            GameError::UnknownTeam(team) => write!(f, "No team with name '{}'", team),
+            GameError::Protocol(message) => write!(f, "Protocol violation: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GameError::Io(err) => Some(err),
+            GameError::Xml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for GameError {
+    fn from(err: std::io::Error) -> Self {
+        GameError::Io(err)
+    }
+}
+
+impl From<xml::reader::Error> for GameError {
+    fn from(err: xml::reader::Error) -> Self {
+        GameError::Xml(err)
+    }
+}
@@ -0,0 +1,270 @@
+use crate::bitboard::Bitboard;
+use crate::board::Board;
+use crate::gamestate::Gamestate;
+use crate::piece::destinations;
+use crate::piece::PieceType;
+use crate::team::Team;
+use crate::vec2::Vec2;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::str::FromStr;
+
+/// Number of weights in [`EvalParams`].
+pub const PARAM_COUNT: usize = 11;
+
+/// Names of the weights, as used in parameter files.
+pub const PARAM_NAMES: [&str; PARAM_COUNT] = [
+    "amber",
+    "moewe",
+    "robbe",
+    "muschel",
+    "seestern",
+    "tower",
+    "tower_attacked",
+    "tower_defended",
+    "advancement",
+    "mobility",
+    "threatened",
+];
+
+/// The weights of the evaluation terms, measured in ambers.
+///
+/// Parameter files hold one `name = value` pair per line, with the names of
+/// [`PARAM_NAMES`]. Empty lines and lines starting with `#` are ignored, missing weights keep
+/// their default.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EvalParams {
+    /// Value of an amber
+    pub amber: f32,
+    /// Value of a single Möwe, and likewise for the other piece types
+    pub moewe: f32,
+    pub robbe: f32,
+    pub muschel: f32,
+    pub seestern: f32,
+    /// Bonus of a double stack, on top of its piece
+    pub tower: f32,
+    /// Value of a tower the opponent can capture, which would earn them an amber
+    pub tower_attacked: f32,
+    /// Value of a tower covered by an own piece
+    pub tower_defended: f32,
+    /// Value of each column a light piece has advanced toward the opponent's base line
+    pub advancement: f32,
+    /// Value of each pseudo-legal move
+    pub mobility: f32,
+    /// Value of a single piece the opponent can capture
+    pub threatened: f32,
+}
+
+pub const DEFAULT_PARAMS: EvalParams = EvalParams {
+    amber: 1.0,
+    moewe: 0.1,
+    robbe: 0.12,
+    muschel: 0.08,
+    seestern: 0.1,
+    tower: 0.05,
+    tower_attacked: -0.4,
+    tower_defended: 0.05,
+    advancement: 0.02,
+    mobility: 0.005,
+    threatened: -0.05,
+};
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_PARAMS
+    }
+}
+
+impl EvalParams {
+    /// Reads the weights from a parameter file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path, err))?
+            .parse()
+    }
+
+    /// The weights in the order of [`PARAM_NAMES`].
+    pub fn values(&self) -> [f32; PARAM_COUNT] {
+        [
+            self.amber,
+            self.moewe,
+            self.robbe,
+            self.muschel,
+            self.seestern,
+            self.tower,
+            self.tower_attacked,
+            self.tower_defended,
+            self.advancement,
+            self.mobility,
+            self.threatened,
+        ]
+    }
+
+    /// The weights in the order of [`PARAM_NAMES`], for changing them by index.
+    pub fn values_mut(&mut self) -> [&mut f32; PARAM_COUNT] {
+        [
+            &mut self.amber,
+            &mut self.moewe,
+            &mut self.robbe,
+            &mut self.muschel,
+            &mut self.seestern,
+            &mut self.tower,
+            &mut self.tower_attacked,
+            &mut self.tower_defended,
+            &mut self.advancement,
+            &mut self.mobility,
+            &mut self.threatened,
+        ]
+    }
+}
+
+impl FromStr for EvalParams {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut params = EvalParams::default();
+
+        for (number, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || format!("Invalid parameter in line {}: '{}'", number + 1, line);
+
+            let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+            let index = PARAM_NAMES
+                .iter()
+                .position(|&known| known == name.trim())
+                .ok_or_else(invalid)?;
+            *params.values_mut()[index] = value.trim().parse().map_err(|_| invalid())?;
+        }
+        Ok(params)
+    }
+}
+
+impl Display for EvalParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value) in PARAM_NAMES.iter().zip(self.values().iter()) {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// How often each term occurs for team one minus how often it occurs for team two, in the
+/// order of [`PARAM_NAMES`]. The evaluation is the dot product of these with the weights.
+pub fn features(gamestate: &Gamestate) -> [f32; PARAM_COUNT] {
+    let one = team_features(&gamestate.board, Team::ONE);
+    let two = team_features(&gamestate.board, Team::TWO);
+
+    let mut out = [0.0; PARAM_COUNT];
+    out[0] = gamestate.ambers(Team::ONE) as f32 - gamestate.ambers(Team::TWO) as f32;
+    for i in 1..PARAM_COUNT {
+        out[i] = one[i] - two[i];
+    }
+    out
+}
+
+/// Evaluates the position from the point of view of team one.
+pub fn evaluate(gamestate: &Gamestate, params: &EvalParams) -> f32 {
+    features(gamestate)
+        .iter()
+        .zip(params.values().iter())
+        .map(|(feature, weight)| feature * weight)
+        .sum()
+}
+
+/// The terms of one team, with the amber term left at zero.
+fn team_features(board: &Board, team: Team) -> [f32; PARAM_COUNT] {
+    let own = board.pieces(team);
+    let (covered, mobility) = attacks(board, team);
+    let (opponent_attacks, _) = attacks(board, team.next());
+
+    let towers = own & board.double_stack;
+    let singles = own & !board.double_stack;
+    let light = own & (board.moewen | board.muscheln | board.seesterne);
+
+    let advancement: u32 = light
+        .iter()
+        .map(|pos| {
+            let x = Vec2::from_pos(pos).x as u32;
+            match team {
+                Team::ONE => x,
+                Team::TWO => 7 - x,
+            }
+        })
+        .sum();
+
+    [
+        0.0,
+        (own & board.moewen).count() as f32,
+        (own & board.robben).count() as f32,
+        (own & board.muscheln).count() as f32,
+        (own & board.seesterne).count() as f32,
+        towers.count() as f32,
+        (towers & opponent_attacks).count() as f32,
+        (towers & covered).count() as f32,
+        advancement as f32,
+        mobility as f32,
+        (singles & opponent_attacks).count() as f32,
+    ]
+}
+
+/// The squares the pieces of the team can move to or cover, and the number of their
+/// pseudo-legal moves.
+fn attacks(board: &Board, team: Team) -> (Bitboard, u32) {
+    let own = board.pieces(team);
+    let mut covered = Bitboard::new();
+    let mut mobility = 0;
+
+    for (piece, pieces) in [
+        (PieceType::MOEWE, board.moewen),
+        (PieceType::ROBBE, board.robben),
+        (PieceType::MUSCHEL, board.muscheln),
+        (PieceType::SEESTERN, board.seesterne),
+    ] {
+        let table = destinations::of(piece);
+        for origin in (pieces & own).iter() {
+            let targets = table[team.index()][origin as usize];
+            covered |= targets;
+            mobility += (targets & !own).count();
+        }
+    }
+    (covered, mobility)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn symmetric_start_is_even() {
+        let mut gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(21));
+        assert_eq!(evaluate(&gamestate, &EvalParams::default()), 0.0);
+
+        gamestate.set_ambers(Team::ONE, 1);
+        assert_eq!(features(&gamestate)[0], 1.0);
+        assert!(evaluate(&gamestate, &EvalParams::default()) > 0.5);
+    }
+
+    #[test]
+    fn parameter_file_round_trip() {
+        let params = EvalParams {
+            mobility: 0.25,
+            tower_attacked: -1.5,
+            ..EvalParams::default()
+        };
+        assert_eq!(params.to_string().parse::<EvalParams>(), Ok(params));
+
+        let partial: EvalParams = "# tuned\n\nrobbe = 0.5\n".parse().unwrap();
+        assert_eq!(partial.robbe, 0.5);
+        assert_eq!(partial.amber, DEFAULT_PARAMS.amber);
+
+        assert!("robbe 0.5".parse::<EvalParams>().is_err());
+        assert!("queen = 9".parse::<EvalParams>().is_err());
+        assert!("robbe = much".parse::<EvalParams>().is_err());
+    }
+}
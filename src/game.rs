@@ -1,211 +1,255 @@
-use std::io::{BufReader, Result};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
 use std::io::{BufWriter, Write};
 use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
+use crate::error::GameError;
 use crate::game_move::Move;
+use crate::game_result::GameResult;
 use crate::gamestate::Gamestate;
+use crate::notation;
+use crate::replay::Recorder;
+use crate::strategy::Strategy;
 use crate::team::Team;
+use crate::time::TimeManager;
 use crate::xml_node::XmlNode;
 use log::debug;
 use xml::EventReader;
 
-#[derive(Debug)]
 pub struct Game {
     pub gamestate: Gamestate,
     pub room_id: String,
     pub stream: TcpStream,
+    /// Reads the messages of the server. It has to live as long as the connection, since the
+    /// whole connection is a single XML document.
+    pub parser: EventReader<BufReader<TcpStream>>,
     pub team: Team,
+    /// Chooses our moves
+    pub strategy: Box<dyn Strategy>,
+    /// Limits the time spent choosing a move
+    pub time: TimeManager,
+    /// Whether the strategy thinks during the opponent's turn
+    pub ponder: bool,
+    /// The position to ponder on while waiting for the opponent's move
+    ponder_position: Option<Gamestate>,
+    /// The position our last move should lead to, checked against the next memento
+    pub expected: Option<Gamestate>,
+    /// Records the game to a replay file
+    pub replay: Option<Recorder<BufWriter<File>>>,
+    /// When the last memento arrived, which is when the current turn started
+    turn_start: Instant,
 }
 
 impl Game {
-    pub fn send_move(&self, r#move: &mut Move) {
-        let final_move = r#move.translate(&self.team);
+    pub fn send_move(&self, final_move: &Move) -> Result<(), GameError> {
         log::info!("Sending move: {}", final_move);
 
-        BufWriter::new(&self.stream).write(format!("<room roomId=\"{}\"><data class=\"move\"><from x=\"{}\" y=\"{}\"/><to x=\"{}\" y=\"{}\"/></data></room>",
+        let mut writer = BufWriter::new(&self.stream);
+        writer.write_all(format!("<room roomId=\"{}\"><data class=\"move\"><from x=\"{}\" y=\"{}\"/><to x=\"{}\" y=\"{}\"/></data></room>",
                                                    &self.room_id,
-                                                   final_move.origin.x,
-                                                   final_move.origin.y,
-                                                   final_move.result.x,
-                                                   final_move.result.y
-        ).as_bytes()).expect("Failed to write move");
+                                                   final_move.origin().x,
+                                                   final_move.origin().y,
+                                                   final_move.result().x,
+                                                   final_move.result().y
+        ).as_bytes())?;
+        writer.flush()?;
+        Ok(())
     }
 
-    fn on_move_request(&mut self) {
+    fn on_move_request(&mut self) -> Result<(), GameError> {
         log::info!("Received MoveRequest");
-        let mut best = self.gamestate.best_move();
-        self.send_move(&mut best);
-    }
-
-    fn on_receive_memento(&mut self, data_node: &XmlNode) {
-        /*
-        let state = data_node
-            .child("state")
-            .expect("Received data node without gamestate");
-
-        //let turn:u8 = data_node.attributes.get("turn").unwrap().get(0).unwrap().parse().unwrap();
-
-        {
-            let last_move = state
-                .child("lastMove")
-                .expect("Failed to find lastMove in state");
-
-            let from = last_move.child("from").unwrap();
-            let to = last_move.child("to").unwrap();
-
-            let from_x = from
-                .attributes
-                .get("x")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<i8>()
-                .unwrap();
-            let from_y = from
-                .attributes
-                .get("y")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<i8>()
-                .unwrap();
-            let to_x = to
-                .attributes
-                .get("x")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<i8>()
-                .unwrap();
-            let to_y = to
-                .attributes
-                .get("y")
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .parse::<i8>()
-                .unwrap();
-
-            let from_pos = position!(from_x, from_y);
-            let to_pos= position!(to_x, to_y);
-
-            self.gamestate.board.apply_anonymous(from_pos, to_pos);
-        }
-
-        {
-            for entry in state.child("ambers").unwrap().children.iter() {
-                let team = entry.child("team").unwrap();
-                let score = entry.child("int").unwrap();
+        let start = Instant::now();
+        let best_move = self
+            .strategy
+            .choose_move(&self.gamestate, self.team, self.time.budget());
+        let elapsed = start.elapsed();
+
+        match best_move {
+            Some(best) => {
+                let (team, strategy) = (self.team, &self.strategy);
+                if let Some(recorder) = self.replay.as_mut() {
+                    let info = strategy.last_search();
+                    if let Err(err) = recorder.record_move(team, &best, elapsed, info) {
+                        log::warn!("Stopped recording the replay: {}", err);
+                        self.replay = None;
+                    }
+                }
 
-                let team = Team::from(&team.data);
-                let score = (&score.data).parse::<u8>().unwrap();
+                let mut expected = self.gamestate;
+                expected.apply(&best);
+                expected.last_move = Some(best);
+                self.expected = Some(expected);
 
-                match team {
-                    Team::ONE => self.gamestate.points.set_left(score),
-                    Team::TWO => self.gamestate.points.set_right(score),
-                }
+                self.send_move(&best)
             }
+            None => Err(GameError::Protocol(
+                "Received a moveRequest without a legal move".to_string(),
+            )),
         }
+    }
 
-         */
-
-        let gamestate_node = data_node.child("state").unwrap();
-
-        let turn = gamestate_node
-            .attributes
-            .get("turn")
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .parse::<u8>()
-            .unwrap();
-
-        let mut gamestate = Gamestate::from(gamestate_node);
-
-        match self.team {
-            Team::ONE => {
-                gamestate.board.rotate90_anti_clockwise();
+    fn on_receive_memento(&mut self, data_node: &XmlNode) -> Result<(), GameError> {
+        let state = data_node.required_child("state")?;
+        let gamestate = match Gamestate::from_memento(state, &self.gamestate) {
+            Ok(gamestate) => gamestate,
+            //Don't lose track of the game just because the last move can't be resolved
+            Err(GameError::Protocol(message)) => {
+                log::warn!("{}", message);
+                Gamestate::try_from(state)?
             }
-            Team::TWO => {
-                gamestate.board.rotate90_clockwise();
-                gamestate
-                    .board
-                    .friendly_pieces
-                    .swap_with(&mut gamestate.board.enemy_pieces);
+            Err(err) => return Err(err),
+        };
+
+        if let Some(expected) = self.expected.take() {
+            if let Err(err) = check_expected(&expected, &gamestate) {
+                log::error!("{}", err);
             }
         }
-        gamestate.board.flip_horizontal();
+        let mover = self.gamestate.current_team();
+        if let (Some(last_move), true) = (gamestate.last_move, mover != self.team) {
+            let elapsed = self.turn_start.elapsed();
+            self.record(|replay| replay.record_move(mover, &last_move, elapsed, None));
+        }
+        self.record(|replay| replay.record_state(&gamestate));
+        self.turn_start = Instant::now();
 
         self.gamestate = gamestate;
+        if self.ponder && !gamestate.is_over() && gamestate.current_team() != self.team {
+            self.ponder_position = Some(gamestate);
+        }
 
         println!(
             "\n[ReceivedMemento | Turn {}]\n{}",
-            turn, self.gamestate.board
+            self.gamestate.round, self.gamestate.board
         );
+        if let Some(last_move) = self.gamestate.last_move {
+            log::info!("Last move: {}", last_move);
+        }
+        log::debug!("Position: {}", notation::write(&self.gamestate));
+        Ok(())
     }
 
-    pub fn game_loop(&mut self) -> std::result::Result<(), GameError> {
-        let copy_of_stream = self.stream.try_clone().unwrap();
-        let mut parser = EventReader::new(BufReader::new(&copy_of_stream));
-
+    /// Handles the messages of the server until the game ends, returning its result.
+    ///
+    /// Only failures of the connection itself end the loop with an error. A malformed or
+    /// unexpected message is logged and skipped, the server will judge the outcome.
+    pub fn game_loop(&mut self) -> Result<GameResult, GameError> {
         loop {
-            let received = XmlNode::read_from(&mut parser);
+            let received = self.receive()?;
 
             match received.name.as_str() {
                 "protocol" => {
+                    return Err(GameError::Protocol(String::from(
+                        "Game ended without a result",
+                    )));
+                }
+                "data" if received.attribute("class").ok() == Some("result") => {
                     log::info!("Ending game");
-                    return Ok(());
+                    let result = GameResult::from_node(&received, self.team)?;
+                    self.record(|replay| replay.record_result(&result));
+                    return Ok(result);
                 }
                 "data" => {
-                    let class = received
-                        .attributes
-                        .get("class")
-                        .expect("Received node without class")
-                        .get(0)
-                        .unwrap();
-
-                    match class.as_str() {
-                        "memento" => {
-                            self.on_receive_memento(&received);
-                        }
-                        "moveRequest" => {
-                            self.on_move_request();
-                        }
-                        "welcomeMessage" => {
-                            panic!("Received multiple welcome messages!")
-                        }
-                        "result" => {
-                            println!("{:?}", received);
-                        }
-                        class => {
-                            panic!("Failed to match class: {}", class)
+                    if let Err(err) = self.on_receive_data(&received) {
+                        match err {
+                            GameError::Io(_) => return Err(err),
+                            err => log::error!("Skipping message: {}", err),
                         }
                     }
                 }
                 name => {
-                    panic!("Failed to match node '{}': {:?}", name , received)
+                    log::warn!("Ignoring unknown node '{}': {:?}", name, received)
                 }
             }
         }
     }
-}
 
-impl Clone for Game {
-    fn clone(&self) -> Self {
-        let gamestate = self.gamestate;
-        let room_id = self.room_id.clone();
-        let stream = self.stream.try_clone().expect("Failed to clone stream");
-        let team = self.team;
+    /// Writes to the replay, if the game is recorded. The recording stops on the first error,
+    /// without affecting the game.
+    fn record<F>(&mut self, write: F)
+    where
+        F: FnOnce(&mut Recorder<BufWriter<File>>) -> io::Result<()>,
+    {
+        if let Some(recorder) = self.replay.as_mut() {
+            if let Err(err) = write(recorder) {
+                log::warn!("Stopped recording the replay: {}", err);
+                self.replay = None;
+            }
+        }
+    }
+
+    /// Reads the next message of the server. During the opponent's turn the strategy ponders
+    /// on its own thread until the message arrives.
+    fn receive(&mut self) -> Result<XmlNode, GameError> {
+        let position = match self.ponder_position.take() {
+            Some(position) => position,
+            None => return XmlNode::read_from(&mut self.parser),
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let strategy = &mut self.strategy;
+        let parser = &mut self.parser;
+        thread::scope(|scope| {
+            let signal = Arc::clone(&stop);
+            let thinker = scope.spawn(move || strategy.ponder(&position, signal));
+
+            let received = XmlNode::read_from(parser);
+            stop.store(true, Ordering::Relaxed);
+            if thinker.join().is_err() {
+                log::error!("Pondering failed");
+            }
+            received
+        })
+    }
 
-        Self {
-            gamestate,
-            room_id,
-            stream,
-            team,
+    fn on_receive_data(&mut self, data_node: &XmlNode) -> Result<(), GameError> {
+        match data_node.attribute("class")? {
+            "memento" => self.on_receive_memento(data_node),
+            "moveRequest" => self.on_move_request(),
+            "welcomeMessage" => Err(GameError::Protocol(
+                "Received multiple welcome messages".to_string(),
+            )),
+            class => Err(GameError::Protocol(format!("Unknown class '{}'", class))),
         }
     }
 }
 
+/// Checks that our move led to the position the server sent.
+fn check_expected(expected: &Gamestate, received: &Gamestate) -> Result<(), GameError> {
+    if expected == received {
+        return Ok(());
+    }
+    Err(GameError::Protocol(format!(
+        "Our move led to a different position than the server's\nExpected: {}\nReceived: {}",
+        notation::write(expected),
+        notation::write(received)
+    )))
+}
+
+impl fmt::Debug for Game {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Game")
+            .field("gamestate", &self.gamestate)
+            .field("room_id", &self.room_id)
+            .field("stream", &self.stream)
+            .field("team", &self.team)
+            .field("strategy", &self.strategy.name())
+            .field("time", &self.time)
+            .field("ponder", &self.ponder)
+            .field("replay", &self.replay.is_some())
+            .field("expected", &self.expected)
+            .finish()
+    }
+}
+
 pub enum Join<'a> {
     ANY,
     ROOM(&'a str),
@@ -213,19 +257,24 @@ pub enum Join<'a> {
 }
 
 impl<'a> Join<'a> {
-    pub fn connect(&self, network_address: &str) -> Result<Game> {
+    /// Joins a game, which will be played by the strategy.
+    pub fn connect(
+        &self,
+        network_address: &str,
+        strategy: Box<dyn Strategy>,
+    ) -> Result<Game, GameError> {
         let stream = TcpStream::connect(network_address)?;
 
         debug!("Connected to server...");
 
-        let mut writer = BufWriter::new(stream.try_clone().expect("Couldn't clone stream"));
+        let mut writer = BufWriter::new(stream.try_clone()?);
 
-        let _sent = match self {
-            Join::ANY => writer.write("<protocol><join/>".as_bytes()),
+        match self {
+            Join::ANY => writer.write_all("<protocol><join/>".as_bytes()),
             Join::ROOM(room_id) => {
-                writer.write(format!("<protocol><joinRoom roomId=\"{}\"/>", room_id).as_bytes())
+                writer.write_all(format!("<protocol><joinRoom roomId=\"{}\"/>", room_id).as_bytes())
             }
-            Join::PREPARED(reservation) => writer.write(
+            Join::PREPARED(reservation) => writer.write_all(
                 format!(
                     "<protocol><joinPrepared reservationCode=\"{}\"/>",
                     reservation
@@ -236,65 +285,140 @@ impl<'a> Join<'a> {
         writer.flush()?;
         debug!("Sent join-request to server");
 
-        let mut parser = EventReader::new(BufReader::new(&stream));
+        let mut parser = EventReader::new(BufReader::new(stream.try_clone()?));
 
-        let joined = XmlNode::read_from(&mut parser);
-        let welcome = XmlNode::read_from(&mut parser);
+        let joined = XmlNode::read_from(&mut parser)?;
+        let welcome = XmlNode::read_from(&mut parser)?;
 
-        let room_id = joined.attributes.get("roomId").unwrap().get(0).unwrap();
-        let my_team: Team = welcome
-            .attributes
-            .get("color")
-            .expect("No attribute named \"color\"")
-            .get(0)
-            .unwrap()
-            .into();
+        let room_id = joined.attribute("roomId")?.to_string();
+        let my_team = welcome.attribute("color")?.parse::<Team>()?;
 
-        let node = XmlNode::read_from(&mut parser);
+        let node = XmlNode::read_from(&mut parser)?;
 
-        match node.attributes.get("class") {
-            None => {}
-            Some(class) => match class[0].as_str() {
-                "memento" => {
-                    let gamestate_node = node.child("state").unwrap();
+        match node.attribute("class")? {
+            "memento" => {
+                let gamestate_node = node.required_child("state")?;
+                let gamestate = Gamestate::try_from(gamestate_node)?;
 
-                    let mut gamestate = Gamestate::from(gamestate_node);
+                let game = Game {
+                    gamestate,
+                    room_id,
+                    stream,
+                    parser,
+                    team: my_team,
+                    strategy,
+                    time: TimeManager::default(),
+                    ponder: true,
+                    ponder_position: None,
+                    expected: None,
+                    replay: None,
+                    turn_start: Instant::now(),
+                };
 
-                    match my_team {
-                        Team::ONE => {
-                            gamestate.board.rotate90_anti_clockwise();
-                        }
-                        Team::TWO => {
-                            gamestate.board.rotate90_clockwise();
-                            gamestate
-                                .board
-                                .friendly_pieces
-                                .swap_with(&mut gamestate.board.enemy_pieces);
-                        }
-                    }
+                log::info!("Joined {} as Team {:?}", game.room_id, game.team);
+                println!("\n[Start]\n{}", game.gamestate.board);
 
-                    let game = Game {
-                        gamestate,
-                        room_id: room_id.clone(),
-                        stream,
-                        team: my_team,
-                    };
+                Ok(game)
+            }
+            "moveRequest" => Err(GameError::Protocol(
+                "Received a moveRequest before the first memento".to_string(),
+            )),
+            class => Err(GameError::Protocol(format!(
+                "Expected the first memento, received '{}'",
+                class
+            ))),
+        }
+    }
+}
 
-                    log::info!("Joined {} as Team {:?}", game.room_id, game.team);
-                    println!("\n[Start]\n{}", game.gamestate.board);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceType;
+    use crate::position;
+    use crate::search::Searcher;
+    use crate::server::write_memento;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::net::TcpListener;
+
+    /// A game of team two, connected to a local listener that never answers.
+    fn local_game(gamestate: Gamestate) -> Game {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        Game {
+            gamestate,
+            room_id: String::from("r"),
+            parser: EventReader::new(BufReader::new(stream.try_clone().unwrap())),
+            stream,
+            team: Team::TWO,
+            strategy: Box::new(Searcher::new()),
+            time: TimeManager::default(),
+            ponder: false,
+            ponder_position: None,
+            replay: None,
+            turn_start: Instant::now(),
+            expected: None,
+        }
+    }
 
-                    return Ok(game);
-                }
-                "moveRequest" => {
-                    log::error!("Received a moveRequest before constructing a game");
-                    panic!()
-                }
-                &_ => {}
-            },
+    const MEMENTO: &str = include_str!("../res/memento.xml");
+
+    #[test]
+    fn reports_an_unexpected_position() {
+        let previous = notation::parse("8/8/8/8/6M1/8/8/7r ONE 0 1 0").unwrap();
+        let state = XmlNode::read_document(MEMENTO.as_bytes()).unwrap();
+        let received =
+            Gamestate::from_memento(state.required_child("state").unwrap(), &previous).unwrap();
+
+        let mut expected = previous;
+        let r#move = Move::new(position!(6, 3), position!(7, 3), PieceType::MOEWE, false);
+        expected.apply(&r#move);
+        expected.last_move = Some(r#move);
+        assert!(check_expected(&expected, &received).is_ok());
+
+        //Our move was another one, which doesn't score
+        let mut expected = previous;
+        let r#move = Move::new(position!(6, 3), position!(6, 4), PieceType::MOEWE, false);
+        expected.apply(&r#move);
+        expected.last_move = Some(r#move);
+        match check_expected(&expected, &received) {
+            Err(GameError::Protocol(message)) => {
+                assert!(message.contains(&notation::write(&expected)), "{}", message);
+                assert!(message.contains(&notation::write(&received)), "{}", message);
+            }
+            result => panic!("Mismatch wasn't reported: {:?}", result),
         }
-        panic!()
     }
-}
 
-pub enum GameError {
+    #[test]
+    fn reports_malformed_messages() {
+        let start = Gamestate::random_start(&mut StdRng::seed_from_u64(10));
+        let mut next = start;
+        next.apply(&start.board.legal_moves()[0]);
+
+        let mut game = local_game(start);
+        let mut receive =
+            |xml: &str| game.on_receive_data(&XmlNode::read_document(xml.as_bytes()).unwrap());
+
+        assert!(matches!(
+            receive("<data class=\"memento\"><state turn=\"1\"><startTeam>ONE</startTeam></state></data>"),
+            Err(GameError::MissingChild { child, .. }) if child == "board"
+        ));
+        assert!(matches!(
+            receive(&write_memento(&next).replace("Moewe", "Queen")),
+            Err(GameError::UnknownPiece(piece)) if piece == "Queen"
+        ));
+        assert!(matches!(
+            receive("<data color=\"ONE\"/>"),
+            Err(GameError::MissingAttribute { attribute, .. }) if attribute == "class"
+        ));
+        assert!(matches!(
+            receive("<data class=\"memento\"/>"),
+            Err(GameError::MissingChild { child, .. }) if child == "state"
+        ));
+
+        receive(&write_memento(&next)).unwrap();
+        assert_eq!(game.gamestate, next);
+    }
 }
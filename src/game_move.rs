@@ -2,71 +2,81 @@
 
 use crate::bitboard::*;
 use crate::piece::PieceType;
-use crate::team::Team;
 use crate::vec2::*;
 use std::fmt::{Display, Formatter};
 
-/// WARNING! Potential bugs with lands_at, because it is not synchronised with Move.
-/// As long as move stays immutable everything is fine.
-#[derive(Clone, Debug, Copy)]
-pub struct Move {
-    pub origin: Vec2,
-    pub vector: Vec2,
-    pub piece: PieceType,
-    pub result: Vec2,
-}
+const SQUARE_MASK: u16 = 0x3F;
+const TO_SHIFT: u16 = 6;
+const PIECE_SHIFT: u16 = 12;
+const CAPTURE_FLAG: u16 = 1 << 14;
+
+/// A move in the server's coordinate system, so it can be sent as it is.
+///
+/// The move is packed into 16 bits: the origin and target positions take 6 bits each,
+/// followed by 2 bits for the piece type and the capture flag.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub struct Move(u16);
 
 impl Move {
-    pub fn new(origin: Vec2, vector: Vec2, piece: PieceType) -> Self {
-        Move {
-            origin,
-            vector,
-            piece,
-            result: origin + vector,
-        }
+    /// Creates a move of the piece from the origin position to the target position. The
+    /// capture flag has to be set if an opposing piece stands on the target.
+    pub const fn new(from: u8, to: u8, piece: PieceType, capture: bool) -> Self {
+        let piece = match piece {
+            PieceType::ROBBE => 0,
+            PieceType::MUSCHEL => 1,
+            PieceType::SEESTERN => 2,
+            PieceType::MOEWE => 3,
+        };
+        let capture = if capture { CAPTURE_FLAG } else { 0 };
+
+        Move(from as u16 | (to as u16) << TO_SHIFT | piece << PIECE_SHIFT | capture)
     }
 
-    pub fn bits(&self) -> Bitboard {
-        let x = self.origin.x + self.vector.x;
-        let y = self.origin.y + self.vector.y;
-        crate::mask_from_coords!(x, y)
+    /// A placeholder move, filling unused slots of a [`MoveList`](crate::move_list::MoveList).
+    pub const fn null() -> Self {
+        Move(0)
     }
 
-    ///Make this nicer when you have the brains to do it.
-    pub fn translate(&self, team: &Team) -> Move {
-        let mut result_board = Bitboard::from(self.result);
-        result_board = result_board.flip_horizontal();
+    /// The position the piece moves from.
+    pub const fn from(&self) -> u8 {
+        (self.0 & SQUARE_MASK) as u8
+    }
 
-        let mut origin_board = Bitboard::from(self.origin);
-        origin_board = origin_board.flip_horizontal();
+    /// The position the piece moves to.
+    pub const fn to(&self) -> u8 {
+        (self.0 >> TO_SHIFT & SQUARE_MASK) as u8
+    }
 
-        match team {
-            Team::ONE => {
-                result_board = result_board.rotate90_clockwise();
-                origin_board = origin_board.rotate90_clockwise();
-            }
-            Team::TWO => {
-                result_board = result_board.rotate90_anti_clockwise();
-                origin_board = origin_board.rotate90_anti_clockwise();
-            }
+    pub const fn piece(&self) -> PieceType {
+        match self.0 >> PIECE_SHIFT & 0x3 {
+            0 => PieceType::ROBBE,
+            1 => PieceType::MUSCHEL,
+            2 => PieceType::SEESTERN,
+            _ => PieceType::MOEWE,
         }
+    }
+
+    /// Whether the move captures an opposing piece.
+    pub const fn is_capture(&self) -> bool {
+        self.0 & CAPTURE_FLAG != 0
+    }
 
-        let origin_coords = crate::coords!(origin_board
-            .get_set_bits()
-            .get(0)
-            .expect("Why the fk can't I plot my vector"));
-        let result_coords = crate::coords!(result_board
-            .get_set_bits()
-            .get(0)
-            .expect("Why the fk can't I plot my vector"));
+    /// The coordinates the piece moves from.
+    pub const fn origin(&self) -> Vec2 {
+        Vec2::from_pos(self.from())
+    }
 
-        let vector = result_coords - origin_coords;
+    /// The coordinates the piece moves to.
+    pub const fn result(&self) -> Vec2 {
+        Vec2::from_pos(self.to())
+    }
 
-        Move::new(origin_coords, vector, self.piece)
+    pub fn vector(&self) -> Vec2 {
+        self.result() - self.origin()
     }
 
-    pub fn out_of_bounds(&self) -> bool {
-        return self.result.x > 7 || self.result.y > 7 || self.result.x < 0 || self.result.y < 0;
+    pub fn bits(&self) -> Bitboard {
+        Bitboard::from(1u64 << self.to())
     }
 }
 
@@ -75,7 +85,9 @@ impl Display for Move {
         write!(
             f,
             "{} moves from {} to {}",
-            self.piece, self.origin, self.result
+            self.piece(),
+            self.origin(),
+            self.result()
         )
     }
 }
@@ -1,37 +1,285 @@
+use crate::error::GameError;
 use crate::team::Team;
+use crate::xml_node::XmlNode;
+use std::fmt;
+use std::fmt::{Display, Formatter};
 
-#[derive(Debug)]
+/// The outcome of a game, as sent by the server in the `result` message.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameResult {
+    /// Outcome from the point of view of our team, with our ambers first
     pub(crate) score: Score,
+    /// The winning team, `None` for a draw
+    pub winner: Option<Team>,
+    /// Score of each team, indexed by [`Team::index`]
+    pub scores: [TeamScore; 2],
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Score {
     DRAW(u8),
     WIN(u8, u8),
     LOSS(u8, u8),
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum Cause<'a> {
+/// The score the server lists for one team.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TeamScore {
+    /// Display name of the player
+    pub name: String,
+    /// Points for the ranking: 2 for a win, 1 for a draw and 0 for a loss
+    pub points: u8,
+    /// Ambers collected during the game
+    pub ambers: u8,
+    pub cause: Cause,
+}
+
+/// Why the game ended for a team. All causes but `Regular` come with the server's reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cause {
     Regular,
-    Left(&'a str),
-    RuleViolation(&'a str),
-    SoftTimeout(&'a str),
-    HardTimeout(&'a str),
+    Left(String),
+    RuleViolation(String),
+    SoftTimeout(String),
+    HardTimeout(String),
 }
 
-impl<'a> Cause<'a> {
-    pub fn from_str(s: &str, reason: &'a str) -> Result<Self, ()> {
+impl Cause {
+    pub fn from_str(s: &str, reason: &str) -> Result<Self, GameError> {
+        let reason = reason.to_string();
         match s {
             "REGULAR" => Ok(Cause::Regular),
             "LEFT" => Ok(Cause::Left(reason)),
             "RULE_VIOLATION" => Ok(Cause::RuleViolation(reason)),
             "SOFT_TIMEOUT" => Ok(Cause::SoftTimeout(reason)),
             "HARD_TIMEOUT" => Ok(Cause::HardTimeout(reason)),
-            s => {
-                panic!("No cause with name {}", s)
-            }
+            s => Err(GameError::InvalidValue {
+                node: String::from("score"),
+                name: String::from("cause"),
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl GameResult {
+    /// Parses the `data` node of class `result`, judging the outcome from the view of `team`.
+    ///
+    /// The node lists an entry per team with the player and its score, whose parts are the
+    /// ranking points followed by the ambers. The winner is missing on a draw.
+    pub fn from_node(node: &XmlNode, team: Team) -> Result<Self, GameError> {
+        let mut scores = [None, None];
+        for entry in &node.required_child("scores")?.children {
+            let player = entry.required_child("player")?;
+            let player_team = player.attribute("team")?.parse::<Team>()?;
+            let score = entry.required_child("score")?;
+
+            let parts = score
+                .children
+                .iter()
+                .map(|part| {
+                    part.data
+                        .trim()
+                        .parse::<u8>()
+                        .map_err(|_| GameError::InvalidValue {
+                            node: String::from("score"),
+                            name: part.name.clone(),
+                            value: part.data.clone(),
+                        })
+                })
+                .collect::<Result<Vec<u8>, GameError>>()?;
+            let (points, ambers) = match parts.as_slice() {
+                [points, ambers, ..] => (*points, *ambers),
+                _ => {
+                    return Err(GameError::MissingChild {
+                        node: String::from("score"),
+                        child: String::from("part"),
+                    })
+                }
+            };
+
+            scores[player_team.index()] = Some(TeamScore {
+                name: player.attribute("name").unwrap_or_default().to_string(),
+                points,
+                ambers,
+                cause: Cause::from_str(
+                    score.attribute("cause")?,
+                    score.attribute("reason").unwrap_or_default(),
+                )?,
+            });
+        }
+
+        let missing = || GameError::Protocol(String::from("Result lacks the score of a team"));
+        let scores = [
+            scores[0].take().ok_or_else(missing)?,
+            scores[1].take().ok_or_else(missing)?,
+        ];
+
+        let winner = match node.child("winner") {
+            Some(winner) => Some(winner.attribute("team")?.parse::<Team>()?),
+            None => None,
+        };
+
+        Ok(GameResult::new(scores, winner, team))
+    }
+
+    /// Creates the result of a game, judging the outcome from the view of `team`.
+    pub fn new(scores: [TeamScore; 2], winner: Option<Team>, team: Team) -> Self {
+        let ours = scores[team.index()].ambers;
+        let theirs = scores[team.next().index()].ambers;
+        let score = match winner {
+            None => Score::DRAW(ours),
+            Some(winner) if winner == team => Score::WIN(ours, theirs),
+            Some(_) => Score::LOSS(ours, theirs),
+        };
+
+        GameResult {
+            score,
+            winner,
+            scores,
+        }
+    }
+}
+
+impl Display for Cause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::Regular => write!(f, "regular"),
+            Cause::Left(reason) => write!(f, "left ({})", reason),
+            Cause::RuleViolation(reason) => write!(f, "rule violation ({})", reason),
+            Cause::SoftTimeout(reason) => write!(f, "soft timeout ({})", reason),
+            Cause::HardTimeout(reason) => write!(f, "hard timeout ({})", reason),
         }
     }
 }
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.winner {
+            Some(winner) => writeln!(f, "Winner: {:?}", winner)?,
+            None => writeln!(f, "Draw")?,
+        }
+        for (team, score) in [Team::ONE, Team::TWO].iter().zip(self.scores.iter()) {
+            writeln!(
+                f,
+                "{:?} ({}): {} points, {} ambers, {}",
+                team, score.name, score.points, score.ambers, score.cause
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A result message in the layout of the server, with team one's score first.
+    fn result_message(
+        one: (&str, &str, u8, u8),
+        two: (&str, &str, u8, u8),
+        winner: &str,
+    ) -> String {
+        let entry = |team: &str, (cause, reason, points, ambers): (&str, &str, u8, u8)| {
+            format!(
+                r#"
+      <entry>
+        <player name="Team {team}" team="{team}"/>
+        <score cause="{cause}" reason="{reason}">
+          <part>{points}</part>
+          <part>{ambers}</part>
+        </score>
+      </entry>"#,
+                team = team,
+                cause = cause,
+                reason = reason,
+                points = points,
+                ambers = ambers
+            )
+        };
+        format!(
+            r#"<data class="result">
+    <definition>
+      <fragment name="Siegpunkte">
+        <aggregation>SUM</aggregation>
+        <relevantForRanking>true</relevantForRanking>
+      </fragment>
+      <fragment name="∅ Bernsteine">
+        <aggregation>AVERAGE</aggregation>
+        <relevantForRanking>true</relevantForRanking>
+      </fragment>
+    </definition>
+    <scores>{}{}
+    </scores>{}
+  </data>"#,
+            entry("ONE", one),
+            entry("TWO", two),
+            winner
+        )
+    }
+
+    fn parse(message: &str, team: Team) -> GameResult {
+        let node = XmlNode::read_document(message.as_bytes()).unwrap();
+        GameResult::from_node(&node, team).unwrap()
+    }
+
+    #[test]
+    fn reads_every_cause() {
+        let causes = [
+            (
+                "LEFT",
+                "Team ONE hat das Spiel verlassen",
+                Cause::Left as fn(String) -> Cause,
+            ),
+            (
+                "RULE_VIOLATION",
+                "Zug von (3, 4) nach (5, 5) ist &quot;ungültig&quot;",
+                Cause::RuleViolation,
+            ),
+            (
+                "SOFT_TIMEOUT",
+                "Zug nach 2150ms gesendet",
+                Cause::SoftTimeout,
+            ),
+            (
+                "HARD_TIMEOUT",
+                "Kein Zug innerhalb von 10s",
+                Cause::HardTimeout,
+            ),
+        ];
+
+        for (cause, reason, expected) in causes {
+            let message = result_message(
+                (cause, reason, 0, 1),
+                ("REGULAR", "", 2, 0),
+                r#"<winner team="TWO"/>"#,
+            );
+            let result = parse(&message, Team::ONE);
+
+            assert_eq!(result.winner, Some(Team::TWO));
+            assert_eq!(result.score, Score::LOSS(1, 0));
+            let reason = reason.replace("&quot;", "\"");
+            assert_eq!(result.scores[0].cause, expected(reason));
+            assert_eq!(result.scores[0].name, "Team ONE");
+            assert_eq!((result.scores[0].points, result.scores[0].ambers), (0, 1));
+            assert_eq!(result.scores[1].cause, Cause::Regular);
+            assert_eq!((result.scores[1].points, result.scores[1].ambers), (2, 0));
+        }
+    }
+
+    #[test]
+    fn reads_a_draw() {
+        let message = result_message(("REGULAR", "", 1, 1), ("REGULAR", "", 1, 1), "");
+        let result = parse(&message, Team::TWO);
+        assert_eq!(result.winner, None);
+        assert_eq!(result.score, Score::DRAW(1));
+        assert_eq!(result.scores[1].name, "Team TWO");
+
+        let unknown = message.replacen("REGULAR", "SURRENDER", 1);
+        let node = XmlNode::read_document(unknown.as_bytes()).unwrap();
+        assert!(matches!(
+            GameResult::from_node(&node, Team::ONE),
+            Err(GameError::InvalidValue { value, .. }) if value == "SURRENDER"
+        ));
+    }
+}
@@ -1,18 +1,35 @@
 #![macro_use]
 
+use crate::board;
 use crate::board::Board;
+use crate::error::GameError;
+use crate::eval;
+use crate::eval::EvalParams;
 use crate::game_move::Move;
+use crate::move_list::MoveList;
 use crate::nibble::Nibble;
+use crate::position;
+use crate::team::Team;
+use crate::vec2::Vec2;
 use crate::xml_node::XmlNode;
-use rand::random;
+use crate::zobrist::ZOBRIST;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Copy, Clone)]
+/// The game ends after 30 rounds, each team moving once per round.
+pub const MAX_TURNS: u8 = 60;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Gamestate {
     pub points: Nibble, //Team 0 is left, Team 1 is right
     pub board: Board,
     pub round: u8,
+    /// The last move as reported by the server. Moves applied locally don't change it.
+    pub last_move: Option<Move>,
 }
 
 impl Gamestate {
@@ -21,93 +38,427 @@ impl Gamestate {
             points: Nibble::new(),
             board: Board::new(),
             round: 0,
+            last_move: None,
         }
     }
 
-    pub fn best_move(&mut self) -> Move {
-        let legal = self.board.legal_moves();
-        let index: usize = random::<usize>() % legal.len();
-        legal[index]
+    /// Creates a fresh game from a random line-up, see [`Board::random_start`].
+    pub fn random_start<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        let mut gamestate = Gamestate::new();
+        gamestate.board = Board::random_start(rng);
+        gamestate
     }
 
-    pub fn alpha_beta(
-        &mut self,
-        depth: u8,
-        mut alpha: f32,
-        mut beta: f32,
-        maximizing_player: bool,
-    ) -> f32 {
-        if depth == 0 || self.is_win() {
-            return self.eval();
+    /// Plays a game of random moves from a random line-up. Returns every position of it,
+    /// with the move leading to it as the last move.
+    #[cfg(test)]
+    pub fn random_game<R: Rng + ?Sized>(rng: &mut R) -> Vec<Gamestate> {
+        let mut gamestate = Gamestate::random_start(rng);
+        let mut positions = vec![gamestate];
+
+        while !gamestate.is_over() {
+            let r#move = match gamestate.board.legal_moves().choose(rng) {
+                Some(r#move) => *r#move,
+                None => break,
+            };
+            gamestate.apply(&r#move);
+            gamestate.last_move = Some(r#move);
+            positions.push(gamestate);
         }
+        positions
+    }
 
-        let legal_moves = self.board.legal_moves();
+    /// Reads the state of a memento, resolving its last move in the position before it.
+    ///
+    /// The server only sends the squares of the last move, so the piece and whether it
+    /// captured are taken from `previous`.
+    pub fn from_memento(state: &XmlNode, previous: &Gamestate) -> Result<Self, GameError> {
+        let mut gamestate = Gamestate::try_from(state)?;
 
-        return if maximizing_player {
-            let mut max_eval = f32::NEG_INFINITY;
-            for game_move in legal_moves {
-                let mut clone = Self::clone(self);
-                clone.board.apply(&game_move);
-                clone.board.rotate180();
-                let eval = clone.alpha_beta(depth - 1, alpha, beta, false);
-                max_eval = f32::max(max_eval, eval);
-                alpha = f32::max(alpha, eval);
-                if beta <= alpha {
-                    break;
-                }
-            }
-            max_eval
-        } else {
-            let mut min_eval = f32::INFINITY;
-            for game_move in legal_moves {
-                let mut clone = Self::clone(self);
-                clone.board.apply(&game_move);
-                clone.board.rotate180();
-                let eval = clone.alpha_beta(depth - 1, alpha, beta, true);
-                min_eval = f32::min(min_eval, eval);
-                beta = f32::min(alpha, eval);
-                if beta <= alpha {
-                    break;
-                }
-            }
-            min_eval
-        };
+        if let Some(last_move) = state.child("lastMove") {
+            let from = parse_square(last_move.required_child("from")?)?;
+            let to = parse_square(last_move.required_child("to")?)?;
+
+            let resolved = previous.board.move_between(from, to).ok_or_else(|| {
+                GameError::Protocol(format!(
+                    "Last move from {} to {} isn't legal in the previous position",
+                    Vec2::from_pos(from),
+                    Vec2::from_pos(to)
+                ))
+            })?;
+            gamestate.last_move = Some(resolved);
+        }
+        Ok(gamestate)
+    }
+
+    /// Whether both states are the same position, regardless of how they were reached.
+    pub fn same_position(&self, other: &Gamestate) -> bool {
+        self.board == other.board && self.points == other.points && self.round == other.round
+    }
+
+    /// The team whose turn it is.
+    pub fn current_team(&self) -> Team {
+        self.board.side_to_move
+    }
+
+    /// Applies a move of the team whose turn it is and credits the ambers it earned.
+    ///
+    /// returns: the number of ambers earned by the move
+    pub fn apply(&mut self, r#move: &Move) -> u8 {
+        let team = self.current_team();
+        let ambers = self.board.apply(r#move);
+        self.add_ambers(team, ambers);
+
+        self.round += 1;
+        ambers
+    }
+
+    /// The amber count of the given team.
+    pub fn ambers(&self, team: Team) -> u8 {
+        match team {
+            Team::ONE => self.points.get_left(),
+            Team::TWO => self.points.get_right(),
+        }
+    }
+
+    /// Sets the amber count of the given team, keeping the hash up to date.
+    pub fn set_ambers(&mut self, team: Team, ambers: u8) {
+        self.board.hash ^= ZOBRIST.ambers(team, self.ambers(team)) ^ ZOBRIST.ambers(team, ambers);
+        match team {
+            Team::ONE => self.points.set_left(ambers),
+            Team::TWO => self.points.set_right(ambers),
+        }
+    }
+
+    fn add_ambers(&mut self, team: Team, ambers: u8) {
+        if ambers > 0 {
+            self.set_ambers(team, self.ambers(team) + ambers);
+        }
+    }
+
+    /// The Zobrist hash of the position, including side to move, amber counts and the turn.
+    pub fn hash(&self) -> u64 {
+        self.board.hash ^ ZOBRIST.turn(self.round)
+    }
+
+    /// Calculates the Zobrist hash from scratch.
+    pub fn calculate_hash(&self) -> u64 {
+        self.board.calculate_hash()
+            ^ ZOBRIST.ambers(Team::ONE, self.points.get_left())
+            ^ ZOBRIST.ambers(Team::TWO, self.points.get_right())
+            ^ ZOBRIST.turn(self.round)
+    }
+
+    /// Applies a move like [`Gamestate::apply`], but records everything needed to take it
+    /// back with [`Gamestate::unmake_move`].
+    pub fn make_move(&mut self, r#move: &Move) -> Undo {
+        let points = self.points;
+        let round = self.round;
+
+        let team = self.current_team();
+        let board = self.board.make_move(r#move);
+        self.add_ambers(team, board.ambers);
+
+        self.round += 1;
+
+        Undo {
+            board,
+            points,
+            round,
+        }
+    }
+
+    /// Takes back a move made with [`Gamestate::make_move`].
+    pub fn unmake_move(&mut self, undo: Undo) {
+        self.board.unmake_move(undo.board);
+        self.points = undo.points;
+        self.round = undo.round;
+    }
+
+    /// Counts the leaf nodes of the game tree up to the given depth. Finished games count as
+    /// leaves, wherever they end.
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        self.perft_with(depth, Board::legal_moves)
+    }
+
+    /// Like [`Gamestate::perft`], generating the moves with the given function.
+    pub fn perft_with(&mut self, depth: u8, generate: fn(&Board) -> MoveList) -> u64 {
+        if depth == 0 || self.is_over() {
+            return 1;
+        }
+
+        let mut nodes = 0;
+        for r#move in generate(&self.board).iter() {
+            let undo = self.make_move(r#move);
+            nodes += self.perft_with(depth - 1, generate);
+            self.unmake_move(undo);
+        }
+        nodes
+    }
+
+    /// Runs perft below every legal move, returning the node count per move.
+    pub fn divide(&mut self, depth: u8) -> Vec<(Move, u64)> {
+        let mut out = Vec::new();
+        for r#move in self.board.legal_moves().iter() {
+            let undo = self.make_move(r#move);
+            out.push((*r#move, self.perft(depth.saturating_sub(1))));
+            self.unmake_move(undo);
+        }
+        out
+    }
+
+    /// Evaluates the position from the point of view of team one, see [`eval::evaluate`].
+    pub fn eval(&self, params: &EvalParams) -> f32 {
+        eval::evaluate(self, params)
     }
 
-    /// Eval function
-    pub fn eval(&self) -> f32 {
-        (self.points.get_left() - self.points.get_right()) as f32
+    /// Whether the game has ended. This is the case when a team holds two ambers at the end
+    /// of a round, or when the turn limit is reached.
+    pub fn is_over(&self) -> bool {
+        if self.round >= MAX_TURNS {
+            return true;
+        }
+        self.round.is_multiple_of(2)
+            && (self.points.get_left() >= 2 || self.points.get_right() >= 2)
     }
 
-    /// Win condition
-    fn is_win(&self) -> bool {
-        self.points.get_left() == 2 || self.points.get_right() == 2
+    /// The team with more ambers, or `None` on a draw. Only meaningful once the game is over.
+    pub fn winner(&self) -> Option<Team> {
+        match self.points.get_left().cmp(&self.points.get_right()) {
+            Ordering::Greater => Some(Team::ONE),
+            Ordering::Less => Some(Team::TWO),
+            Ordering::Equal => None,
+        }
     }
 }
 
+/// Record of a move made with [`Gamestate::make_move`].
+#[derive(Debug, Copy, Clone)]
+pub struct Undo {
+    board: board::Undo,
+    points: Nibble,
+    round: u8,
+}
+
 impl Display for Gamestate {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.board)
     }
 }
 
-impl From<&XmlNode> for Gamestate {
-    fn from(node: &XmlNode) -> Self {
+impl TryFrom<&XmlNode> for Gamestate {
+    type Error = GameError;
+
+    fn try_from(node: &XmlNode) -> Result<Self, Self::Error> {
         let mut gamestate = Gamestate::new();
-        let turn: u8 = node
-            .attributes
-            .get("turn")
-            .unwrap()
-            .get(0)
-            .unwrap()
-            .parse()
-            .unwrap();
+        let turn = node.parse_attribute::<u8>("turn")?;
 
         gamestate.round = turn;
 
-        let board_node = node.child("board").unwrap();
-        gamestate.board = Board::from(board_node);
+        let start_team = match node.child("startTeam") {
+            Some(team) => team.data.parse::<Team>()?,
+            None => Team::ONE,
+        };
+
+        let board_node = node.required_child("board")?;
+        gamestate.board = Board::try_from(board_node)?;
+        gamestate.board.side_to_move = Team::current(start_team, turn);
+        gamestate.board.hash = gamestate.board.calculate_hash();
 
-        gamestate
+        if let Some(ambers) = node.child("ambers") {
+            for entry in &ambers.children {
+                let team = entry.required_child("team")?.data.parse::<Team>()?;
+                let count = entry.required_child("int")?;
+                let count = count
+                    .data
+                    .parse::<u8>()
+                    .map_err(|_| GameError::InvalidValue {
+                        node: String::from("ambers"),
+                        name: count.name.clone(),
+                        value: count.data.clone(),
+                    })?;
+                gamestate.set_ambers(team, count);
+            }
+        }
+
+        Ok(gamestate)
+    }
+}
+
+/// Reads the square of a `from` or `to` node.
+pub fn parse_square(node: &XmlNode) -> Result<u8, GameError> {
+    let x = board::parse_coordinate(node, "x")?;
+    let y = board::parse_coordinate(node, "y")?;
+    Ok(position!(x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+    use crate::piece::PieceType;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// The memento after team one's Möwe scored its second amber on the base line, starting
+    /// from `8/8/8/8/6M1/8/8/7r ONE 0 1 0`.
+    const MEMENTO: &str = include_str!("../res/memento.xml");
+
+    #[test]
+    fn reads_the_last_move_and_ambers() {
+        let previous = notation::parse("8/8/8/8/6M1/8/8/7r ONE 0 1 0").unwrap();
+        let data = XmlNode::read_document(MEMENTO.as_bytes()).unwrap();
+        let state = data.required_child("state").unwrap();
+        let gamestate = Gamestate::from_memento(state, &previous).unwrap();
+
+        assert_eq!(gamestate.points.get_left(), 2);
+        assert_eq!(gamestate.points.get_right(), 0);
+        assert_eq!(gamestate.hash(), gamestate.calculate_hash());
+        let r#move = Move::new(position!(6, 3), position!(7, 3), PieceType::MOEWE, false);
+        assert_eq!(gamestate.last_move, Some(r#move));
+        assert_eq!(gamestate.current_team(), Team::TWO);
+
+        let mut applied = previous;
+        applied.apply(&r#move);
+        applied.last_move = Some(r#move);
+        assert_eq!(applied, gamestate);
+
+        //The last move has to be legal in the previous position
+        let elsewhere = notation::parse("8/8/8/8/8/6M1/8/7r ONE 0 1 0").unwrap();
+        assert!(matches!(
+            Gamestate::from_memento(state, &elsewhere),
+            Err(GameError::Protocol(_))
+        ));
+    }
+
+    /// Plays random games and checks at every node that the table based move generation
+    /// produces the same moves as the vector based reference.
+    #[test]
+    fn table_moves_match_vector_moves() {
+        let mut rng = StdRng::seed_from_u64(0x7AB1E);
+
+        for _ in 0..200 {
+            for gamestate in Gamestate::random_game(&mut rng) {
+                let mut tables = gamestate.board.legal_moves();
+                let mut vectors = gamestate.board.legal_moves_by_vectors();
+
+                let key = |r#move: &Move| (r#move.from(), r#move.to());
+                tables.sort_by_key(key);
+                vectors.sort_by_key(key);
+                assert_eq!(
+                    tables, vectors,
+                    "Move generators disagree on\n{}",
+                    gamestate
+                );
+            }
+        }
+    }
+
+    /// Plays random games and checks at every node that make/unmake agrees with
+    /// clone-and-apply, that the incremental hash matches a fresh one, and that unmaking
+    /// restores the original state exactly.
+    #[test]
+    fn make_unmake_matches_clone_and_apply() {
+        let mut rng = StdRng::seed_from_u64(0x05EE);
+
+        for _ in 0..200 {
+            for mut gamestate in Gamestate::random_game(&mut rng) {
+                for r#move in gamestate.board.legal_moves().iter() {
+                    let before = gamestate;
+
+                    let mut applied = gamestate;
+                    let ambers = applied.apply(r#move);
+
+                    let undo = gamestate.make_move(r#move);
+                    assert_eq!(gamestate, applied, "make_move diverged for {}", r#move);
+                    assert_eq!(undo.board.ambers, ambers);
+                    assert_eq!(before.board.ambers_of(r#move), ambers);
+                    assert_eq!(gamestate.hash(), gamestate.calculate_hash());
+
+                    gamestate.unmake_move(undo);
+                    assert_eq!(gamestate, before, "unmake_move diverged for {}", r#move);
+                }
+            }
+        }
+    }
+
+    /// A state node at the turn, holding a Möwe of each team and the start team.
+    fn state_node(turn: u8, start_team: &str) -> XmlNode {
+        let node = |name: &str| XmlNode {
+            name: name.to_string(),
+            ..XmlNode::new()
+        };
+        let attribute = |node: &mut XmlNode, name: &str, value: &str| {
+            node.attributes
+                .insert(name.to_string(), vec![value.to_string()]);
+        };
+
+        let mut pieces = node("pieces");
+        for (x, team) in [("0", "ONE"), ("7", "TWO")] {
+            let mut coordinates = node("coordinates");
+            attribute(&mut coordinates, "x", x);
+            attribute(&mut coordinates, "y", "3");
+            let mut piece = node("piece");
+            attribute(&mut piece, "type", "Moewe");
+            attribute(&mut piece, "team", team);
+            attribute(&mut piece, "count", "1");
+
+            let mut entry = node("entry");
+            entry.children = vec![coordinates, piece];
+            pieces.children.push(entry);
+        }
+        let mut board = node("board");
+        board.children.push(pieces);
+        let mut start = node("startTeam");
+        start.data = start_team.to_string();
+
+        let mut state = node("state");
+        attribute(&mut state, "turn", &turn.to_string());
+        state.children = vec![start, board];
+        state
+    }
+
+    #[test]
+    fn reads_the_start_team() {
+        let state = |turn: u8, start_team: &str| {
+            Gamestate::try_from(&state_node(turn, start_team)).unwrap()
+        };
+
+        assert_eq!(state(0, "TWO").current_team(), Team::TWO);
+        assert_eq!(state(3, "TWO").current_team(), Team::ONE);
+        assert_eq!(state(4, "TWO").current_team(), Team::TWO);
+        assert_eq!(state(0, "ONE").current_team(), Team::ONE);
+        assert_eq!(state(3, "ONE").current_team(), Team::TWO);
+
+        let moved = state(0, "TWO");
+        assert!(moved
+            .board
+            .legal_moves()
+            .iter()
+            .all(|r#move| moved.board.owner_at(r#move.from()) == Some(Team::TWO)));
+    }
+
+    #[test]
+    fn rejects_incomplete_states() {
+        let read =
+            |xml: &str| Gamestate::try_from(&XmlNode::read_document(xml.as_bytes()).unwrap());
+
+        assert!(matches!(
+            read("<state turn=\"2\"><startTeam>ONE</startTeam></state>"),
+            Err(GameError::MissingChild { node, child }) if node == "state" && child == "board"
+        ));
+        assert!(matches!(
+            read("<state><board><pieces/></board></state>"),
+            Err(GameError::MissingAttribute { attribute, .. }) if attribute == "turn"
+        ));
+        assert!(matches!(
+            read("<state turn=\"two\"><board><pieces/></board></state>"),
+            Err(GameError::InvalidValue { value, .. }) if value == "two"
+        ));
+        assert!(matches!(
+            read("<state turn=\"0\"><startTeam>BLUE</startTeam><board><pieces/></board></state>"),
+            Err(GameError::UnknownTeam(team)) if team == "BLUE"
+        ));
+        assert!(read("<state turn=\"0\"><board><pieces/></board></state>").is_ok());
     }
 }
@@ -1,42 +1,132 @@
 #![allow(unused)]
 #![allow(dead_code)]
-use crate::game::GameError;
+#![allow(clippy::upper_case_acronyms)]
+use crate::cli::Options;
+use crate::error::GameError;
 use crate::game_result::GameResult;
+use crate::replay::Recorder;
 use env_logger::Builder;
 use game::Join;
 use log::LevelFilter;
 use std::env;
+use std::process;
 
+mod bench;
 mod bitboard;
 mod board;
+mod cli;
+mod error;
+mod eval;
 mod game;
 mod game_move;
 mod game_result;
 mod gamestate;
+mod match_runner;
+mod mcts;
+mod move_list;
 mod nibble;
+mod notation;
+mod official_replay;
+mod perft;
 mod piece;
+mod replay;
+mod search;
+mod server;
+mod sprt;
+mod strategy;
 mod team;
+mod time;
+mod transposition;
+mod tune;
 mod vec2;
 mod xml_node;
+mod zobrist;
 
 fn main() {
-    Builder::new()
-        .parse_env(&env::var("MY_APP_LOG").unwrap_or_default())
-        .filter_level(LevelFilter::Info)
-        .init();
+    //Developer tools, run with `cargo run --release -- <command>`
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("bench") => {
+            init_logger(LevelFilter::Info);
+            bench::run();
+            return;
+        }
+        Some("perft") => {
+            init_logger(LevelFilter::Info);
+            perft::run(&args[2..]);
+            return;
+        }
+        Some("match") => {
+            init_logger(LevelFilter::Warn);
+            match_runner::run(&args[2..]);
+            return;
+        }
+        Some("sprt") => {
+            init_logger(LevelFilter::Warn);
+            sprt::run(&args[2..]);
+            return;
+        }
+        Some("server") => {
+            init_logger(LevelFilter::Info);
+            server::run(&args[2..]);
+            return;
+        }
+        Some("replay") => {
+            init_logger(LevelFilter::Warn);
+            official_replay::run(&args[2..]);
+            return;
+        }
+        Some("tune") => {
+            init_logger(LevelFilter::Warn);
+            tune::run(&args[2..]);
+            return;
+        }
+        _ => {}
+    }
 
-    let mut game = Join::ANY
-        .connect("localhost:13050")
-        .expect("Connection failed");
+    let options = match Options::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(err) => cli::exit_with_usage(&err, cli::USAGE),
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    init_logger(options.log_level);
+
+    let strategy = options.strategy.build(&options.engine);
+    let mut game = match options.join().connect(&options.address(), strategy) {
+        Ok(game) => game,
+        Err(err) => {
+            log::error!("Failed to join a game: {}", err);
+            process::exit(1);
+        }
+    };
+    game.time = options.time;
+    game.ponder = options.ponder;
+    if let Some(directory) = &options.replay {
+        match Recorder::create(directory, &game.room_id, game.team, &game.gamestate) {
+            Ok(recorder) => game.replay = Some(recorder),
+            Err(err) => log::warn!("Failed to start recording the replay: {}", err),
+        }
+    }
 
     let result = game.game_loop();
 
     match result {
         Ok(res) => {
-            log::info!("{:?}", res);
+            log::info!("Game over\n{}", res);
         }
         Err(err) => {
-            log::error!("Network error! {:?}", err);
+            log::error!("Network error! {}", err);
+            process::exit(1);
         }
     }
 }
+
+fn init_logger(level: LevelFilter) {
+    Builder::new()
+        .parse_env(env::var("MY_APP_LOG").unwrap_or_default())
+        .filter_level(level)
+        .init();
+}
@@ -0,0 +1,454 @@
+use crate::cli;
+use crate::cli::Flags;
+use crate::eval::EvalParams;
+use crate::gamestate::Gamestate;
+use crate::search::MAX_TT_BITS;
+use crate::strategy::{Strategy, StrategyKind, StrategyOptions};
+use crate::team::Team;
+use crate::time::TimeManager;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Number of random plies played from the starting position before the engines take over.
+pub const OPENING_PLIES: u8 = 4;
+
+/// A search configuration taking part in a match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Engine {
+    pub name: String,
+    pub strategy: StrategyKind,
+    /// Time budget of a move. The alpha-beta search only uses all of it when its best move
+    /// keeps changing, see [`Allocation`](crate::time::Allocation).
+    pub move_time: Duration,
+    pub options: StrategyOptions,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            name: String::from("default"),
+            strategy: StrategyKind::AlphaBeta,
+            move_time: TimeManager::default().budget(),
+            options: StrategyOptions::default(),
+        }
+    }
+}
+
+impl Engine {
+    /// Parses an engine given as comma separated `key=value` pairs, like `time=50,tt=18`.
+    /// The keys are `name`, `strategy` as in [`StrategyKind`], `time` for the time budget of a
+    /// move in milliseconds, which the alpha-beta search shares out as in
+    /// [`Allocation`](crate::time::Allocation), `tt` for the size of the transposition table
+    /// as a power of two, `playout` for the playout policy of the MCTS and `eval` for a file
+    /// of evaluation weights. Missing keys keep their default.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut engine = Engine {
+            name: spec.to_string(),
+            ..Engine::default()
+        };
+
+        for pair in spec.split(',').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected 'key=value' in engine '{}'", spec))?;
+            let invalid = || format!("Invalid value '{}' for '{}'", value, key);
+
+            match key {
+                "name" => engine.name = value.to_string(),
+                "strategy" => engine.strategy = value.parse()?,
+                "time" => {
+                    engine.move_time = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+                }
+                "tt" => match value.parse() {
+                    Ok(bits @ 1..=MAX_TT_BITS) => engine.options.tt_bits = bits,
+                    _ => return Err(invalid()),
+                },
+                "playout" => engine.options.playout = value.parse()?,
+                "eval" => engine.options.params = EvalParams::load(value)?,
+                key => return Err(format!("Unknown engine option '{}'", key)),
+            }
+        }
+        Ok(engine)
+    }
+
+    fn strategy(&self) -> Box<dyn Strategy> {
+        self.strategy.build(&self.options)
+    }
+}
+
+/// The course of a single game of a match.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GameRecord {
+    /// The team played by the first engine
+    pub first: Team,
+    pub winner: Option<Team>,
+    /// Amber count of each team, indexed by [`Team::index`]
+    pub ambers: [u8; 2],
+    /// Turns played, including the opening
+    pub turns: u8,
+}
+
+impl GameRecord {
+    /// The points of the first engine: 1 for a win, 0.5 for a draw and 0 for a loss.
+    pub fn score(&self) -> f64 {
+        match self.winner {
+            None => 0.5,
+            Some(winner) if winner == self.first => 1.0,
+            Some(_) => 0.0,
+        }
+    }
+}
+
+/// Wins, draws and losses of the first engine of a match against the second.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Tally {
+    pub fn add(&mut self, record: &GameRecord) {
+        match record.winner {
+            None => self.draws += 1,
+            Some(winner) if winner == record.first => self.wins += 1,
+            Some(_) => self.losses += 1,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The average points per game, between 0 and 1. Without games it is even.
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + 0.5 * self.draws as f64) / self.games() as f64
+    }
+
+    /// The Elo difference of the first engine to the second, with the margin of its 95%
+    /// confidence interval. Both are infinite as long as one side hasn't scored, and the margin
+    /// is infinite while the interval reaches a score of 0 or 1. Without games the difference
+    /// is 0 with an infinite margin.
+    pub fn elo(&self) -> (f64, f64) {
+        if self.games() == 0 {
+            return (0.0, f64::INFINITY);
+        }
+        let games = self.games() as f64;
+        let score = self.score();
+
+        let deviation = |result: f64, count: u32| count as f64 * (result - score).powi(2);
+        let variance =
+            (deviation(1.0, self.wins) + deviation(0.5, self.draws) + deviation(0.0, self.losses))
+                / games;
+        let error = 1.96 * (variance / games).sqrt();
+
+        let elo = elo_difference(score);
+        if !elo.is_finite() {
+            return (elo, f64::INFINITY);
+        }
+        let upper = elo_difference((score + error).min(1.0));
+        let lower = elo_difference((score - error).max(0.0));
+        let margin = (upper - lower) / 2.0;
+        (elo, margin)
+    }
+}
+
+/// The Elo difference that makes the given average score expected.
+pub fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Creates a starting position with random line-ups, followed by random opening moves.
+pub fn random_opening<R: Rng + ?Sized>(rng: &mut R, plies: u8) -> Gamestate {
+    let mut gamestate = Gamestate::random_start(rng);
+
+    for _ in 0..plies {
+        if gamestate.is_over() {
+            break;
+        }
+        match gamestate.board.legal_moves().choose(rng) {
+            Some(r#move) => gamestate.apply(r#move),
+            None => break,
+        };
+    }
+    gamestate
+}
+
+/// Plays a game from the opening, with `engines` indexed by the team they play.
+///
+/// returns: the positions of the game, from the opening to the final one
+pub fn play_game(engines: [&Engine; 2], opening: Gamestate) -> Vec<Gamestate> {
+    let mut strategies = [engines[0].strategy(), engines[1].strategy()];
+    let mut gamestate = opening;
+    let mut positions = vec![opening];
+
+    while !gamestate.is_over() {
+        let team = gamestate.current_team();
+        let engine = team.index();
+        let best_move = strategies[engine].choose_move(&gamestate, team, engines[engine].move_time);
+        match best_move {
+            //Reported like the server does, so strategies can follow the game
+            Some(r#move) => {
+                gamestate.apply(&r#move);
+                gamestate.last_move = Some(r#move);
+                positions.push(gamestate);
+            }
+            None => break,
+        }
+    }
+    positions
+}
+
+/// Plays up to `games` games between the engines on `threads` threads. Every opening is
+/// played twice, with the engines swapping colours, so the number of games is rounded up to an
+/// even number.
+///
+/// `report` is called after every finished game and can end the match early by returning
+/// `false`. Games still running at that point are played to the end, but not reported.
+pub fn play_match<F>(
+    engines: [&Engine; 2],
+    games: u32,
+    threads: usize,
+    seed: u64,
+    mut report: F,
+) -> Vec<GameRecord>
+where
+    F: FnMut(&GameRecord) -> bool,
+{
+    let games = (games as usize).div_ceil(2) * 2;
+
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut records = Vec::with_capacity(games);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let game = next.fetch_add(1, Ordering::Relaxed);
+                if game >= games {
+                    break;
+                }
+
+                //Even games give the first engine team one, odd games team two
+                let first = if game.is_multiple_of(2) {
+                    Team::ONE
+                } else {
+                    Team::TWO
+                };
+                let by_team = match first {
+                    Team::ONE => [engines[0], engines[1]],
+                    Team::TWO => [engines[1], engines[0]],
+                };
+
+                let positions = play_game(by_team, opening(seed, game / 2));
+                let end = positions[positions.len() - 1];
+                let record = GameRecord {
+                    first,
+                    winner: end.winner(),
+                    ambers: [end.ambers(Team::ONE), end.ambers(Team::TWO)],
+                    turns: end.round,
+                };
+                if sender.send(record).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for record in receiver.iter() {
+            records.push(record);
+            if !report(&record) {
+                //Stops the workers once their current game is over
+                next.store(games, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
+    records
+}
+
+/// The opening of the given pair of games. It only depends on the seed, so matches with the
+/// same seed start from the same positions. The pair is spread over the bits of the seed, so
+/// that matches with neighbouring seeds don't share their openings.
+pub fn opening(seed: u64, pair: usize) -> Gamestate {
+    let mut rng = StdRng::seed_from_u64(seed ^ (pair as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    random_opening(&mut rng, OPENING_PLIES)
+}
+
+/// Parses the two engines leading the arguments of a command, as in [`Engine::parse`].
+pub fn parse_engines(args: &[String]) -> Result<[Engine; 2], String> {
+    match (args.first(), args.get(1)) {
+        (Some(first), Some(second)) => Ok([Engine::parse(first)?, Engine::parse(second)?]),
+        _ => Err(String::from("Two engines are required")),
+    }
+}
+
+/// Entry point of the `match` command: `match <engine> <engine> [--games <n>]
+/// [--threads <n>] [--seed <n>]`, with the engines as described in [`Engine::parse`].
+pub fn run(args: &[String]) {
+    let usage = "Usage: match <engine> <engine> [--games <n>] [--threads <n>] [--seed <n>]\n\
+                 Engines are given as 'key=value' pairs, like 'name=new,time=50,tt=18'";
+
+    let engines = match parse_engines(args) {
+        Ok(engines) => engines,
+        Err(err) => cli::exit_with_usage(&err, usage),
+    };
+
+    let mut games = 100u32;
+    let mut threads = 1usize;
+    let mut seed = 0u64;
+
+    let mut flags = Flags::new(&args[2..]);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--games" => flags.parse().map(|value| games = value),
+            "--threads" => flags.parse().map(|value| threads = value),
+            "--seed" => flags.parse().map(|value| seed = value),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, usage);
+        }
+    }
+
+    println!(
+        "{} vs {}, {} games on {} threads",
+        engines[0].name, engines[1].name, games, threads
+    );
+
+    let mut tally = Tally::default();
+    let records = play_match([&engines[0], &engines[1]], games, threads, seed, |record| {
+        tally.add(record);
+        println!(
+            "Game {:>4}: {} as {:?} scores {} in {} turns ({}:{}) | W/D/L {}/{}/{}",
+            tally.games(),
+            engines[0].name,
+            record.first,
+            record.score(),
+            record.turns,
+            record.ambers[0],
+            record.ambers[1],
+            tally.wins,
+            tally.draws,
+            tally.losses
+        );
+        true
+    });
+
+    let count = records.len().max(1) as f64;
+    let ambers = records
+        .iter()
+        .map(|record| record.ambers[record.first.index()] as f64)
+        .sum::<f64>()
+        / count;
+    let opponent_ambers = records
+        .iter()
+        .map(|record| record.ambers[record.first.next().index()] as f64)
+        .sum::<f64>()
+        / count;
+    let turns = records
+        .iter()
+        .map(|record| record.turns as f64)
+        .sum::<f64>()
+        / count;
+    let (elo, margin) = tally.elo();
+
+    println!(
+        "\n{} vs {}: W/D/L {}/{}/{} | Score {:.1}% | Elo {:+.1} ± {:.1}",
+        engines[0].name,
+        engines[1].name,
+        tally.wins,
+        tally.draws,
+        tally.losses,
+        tally.score() * 100.0,
+        elo,
+        margin
+    );
+    println!(
+        "Average ambers {:.2}:{:.2} | Average length {:.1} turns",
+        ambers, opponent_ambers, turns
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    #[test]
+    fn elo_of_tally() {
+        let tally = Tally {
+            wins: 60,
+            draws: 30,
+            losses: 10,
+        };
+        let (elo, margin) = tally.elo();
+        assert!((elo - 190.8).abs() < 0.1, "{}", elo);
+        assert!(margin > 0.0 && margin < elo, "{}", margin);
+
+        let even = Tally {
+            wins: 10,
+            draws: 0,
+            losses: 10,
+        };
+        assert_eq!(even.elo().0, 0.0);
+
+        let empty = Tally::default();
+        assert_eq!(empty.score(), 0.5);
+        assert_eq!(empty.elo(), (0.0, f64::INFINITY));
+    }
+
+    #[test]
+    fn margin_of_a_lopsided_tally() {
+        //The interval of the score reaches past 1, which has no Elo difference
+        let tally = Tally {
+            wins: 9,
+            draws: 0,
+            losses: 1,
+        };
+        let (elo, margin) = tally.elo();
+        assert!(elo.is_finite() && elo > 0.0, "{}", elo);
+        assert_eq!(margin, f64::INFINITY);
+
+        let flawless = Tally {
+            wins: 5,
+            draws: 0,
+            losses: 0,
+        };
+        assert_eq!(flawless.elo(), (f64::INFINITY, f64::INFINITY));
+    }
+
+    #[test]
+    fn neighbouring_seeds_have_different_openings() {
+        let openings = |seed| (0..50).map(move |pair| opening(seed, pair));
+        assert!(openings(3).zip(openings(3)).all(|(a, b)| a == b));
+
+        let first: Vec<Gamestate> = openings(0).collect();
+        for other in openings(1) {
+            assert!(!first.contains(&other), "{}", notation::write(&other));
+        }
+    }
+
+    #[test]
+    fn alternates_colours() {
+        let engine = Engine::parse("time=1,tt=10").unwrap();
+        let records = play_match([&engine, &engine], 4, 2, 1, |_| true);
+
+        assert_eq!(records.len(), 4);
+        let as_one = records.iter().filter(|r| r.first == Team::ONE).count();
+        assert_eq!(as_one, 2);
+        for record in records {
+            assert!(record.turns >= OPENING_PLIES);
+        }
+    }
+}
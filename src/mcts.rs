@@ -0,0 +1,462 @@
+use crate::board::Board;
+use crate::game_move::Move;
+use crate::gamestate::Gamestate;
+use crate::move_list::MoveList;
+use crate::strategy::Strategy;
+use crate::team::Team;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Exploration constant of the UCT formula.
+pub const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// Chance of the light playout policy to capture when it can't earn an amber.
+const CAPTURE_PROBABILITY: f64 = 0.5;
+
+/// How the moves of a playout are chosen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Playout {
+    /// Uniformly random moves
+    Random,
+    /// Moves earning ambers whenever possible and captures half of the time, otherwise
+    /// random moves
+    Light,
+}
+
+impl FromStr for Playout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Playout::Random),
+            "light" => Ok(Playout::Light),
+            s => Err(format!("No playout policy named '{}'", s)),
+        }
+    }
+}
+
+/// A node of the search tree, reached by playing `r#move` in its parent.
+#[derive(Debug, Clone)]
+struct Node {
+    r#move: Move,
+    /// The team that played `r#move`, whose view `score` is from
+    team: Team,
+    children: Vec<usize>,
+    /// Legal moves without a child yet
+    untried: MoveList,
+    visits: u32,
+    /// Sum of the playout results: 1 for a win, 0.5 for a draw and 0 for a loss
+    score: f64,
+}
+
+impl Node {
+    fn new(r#move: Move, team: Team, gamestate: &Gamestate) -> Self {
+        let untried = if gamestate.is_over() {
+            MoveList::new()
+        } else {
+            gamestate.board.legal_moves()
+        };
+
+        Node {
+            r#move,
+            team,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            score: 0.0,
+        }
+    }
+
+    /// The UCT value of the node. Unvisited nodes come first, as they have no average yet.
+    fn uct(&self, parent_visits: u32) -> f64 {
+        if self.visits == 0 {
+            return f64::INFINITY;
+        }
+        let visits = self.visits as f64;
+        self.score / visits + EXPLORATION * ((parent_visits as f64).ln() / visits).sqrt()
+    }
+}
+
+/// Monte Carlo tree search with UCT selection.
+///
+/// The tree is kept between searches. When the next search starts from the position reached
+/// by our last move and the opponent's reply, as reported in the memento, the subtree below
+/// that reply is searched on.
+#[derive(Debug, Clone)]
+pub struct Mcts {
+    /// The tree, with the root at index 0
+    nodes: Vec<Node>,
+    /// The position at the root of the tree
+    root_state: Option<Gamestate>,
+    /// The move returned by the last search
+    played: Option<Move>,
+    pub playout: Playout,
+    rng: StdRng,
+}
+
+impl Mcts {
+    pub fn new(playout: Playout) -> Self {
+        Mcts::with_seed(playout, rand::random())
+    }
+
+    pub fn with_seed(playout: Playout, seed: u64) -> Self {
+        Mcts {
+            nodes: Vec::new(),
+            root_state: None,
+            played: None,
+            playout,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Runs playouts from the position until the budget is used up, and returns the most
+    /// visited move.
+    pub fn search(&mut self, gamestate: &Gamestate, budget: Duration) -> Option<Move> {
+        let deadline = Instant::now() + budget;
+
+        if self.reuse_tree(gamestate) {
+            log::info!(
+                "Reusing the tree with {} playouts",
+                self.nodes.first().map_or(0, |root| root.visits)
+            );
+        } else {
+            self.new_tree(gamestate);
+        }
+
+        let mut iterations = 0u32;
+        //At least one iteration, so that there is a move to return
+        while iterations == 0 || Instant::now() < deadline {
+            self.iterate(gamestate);
+            iterations += 1;
+        }
+
+        let root = &self.nodes[0];
+        let best = root
+            .children
+            .iter()
+            .map(|&child| &self.nodes[child])
+            .max_by_key(|child| child.visits)?;
+        log::info!(
+            "{} playouts, best move visited {} times with score {:.3}",
+            iterations,
+            best.visits,
+            best.score / best.visits as f64
+        );
+        let best = best.r#move;
+        self.played = Some(best);
+        Some(best)
+    }
+
+    /// Grows the tree during the opponent's turn, until `stop` is set. The tree is moved down
+    /// along our last move first, so that the next search keeps the subtree below the reply
+    /// the opponent plays.
+    pub fn ponder(&mut self, gamestate: &Gamestate, stop: Arc<AtomicBool>) {
+        if let Some(played) = self.played.take() {
+            self.advance(&played);
+        }
+        if !self
+            .root_state
+            .is_some_and(|state| state.same_position(gamestate))
+        {
+            self.new_tree(gamestate);
+        }
+
+        let mut iterations = 0u32;
+        while !stop.load(Ordering::Relaxed) {
+            self.iterate(gamestate);
+            iterations += 1;
+        }
+        log::debug!("Pondered {} playouts", iterations);
+    }
+
+    /// Starts over with the position at the root.
+    fn new_tree(&mut self, gamestate: &Gamestate) {
+        self.nodes.clear();
+        self.nodes.push(Node::new(
+            Move::null(),
+            gamestate.current_team().next(),
+            gamestate,
+        ));
+        self.root_state = Some(*gamestate);
+    }
+
+    /// Moves the root of the tree down along the move, keeping the statistics below it.
+    /// If the move hasn't been expanded yet, the tree is dropped.
+    ///
+    /// returns: whether the subtree could be kept
+    pub fn advance(&mut self, r#move: &Move) -> bool {
+        let child = self.nodes.first().and_then(|root| {
+            root.children
+                .iter()
+                .copied()
+                .find(|&child| self.nodes[child].r#move == *r#move)
+        });
+
+        match (child, self.root_state.as_mut()) {
+            (Some(child), Some(state)) => {
+                state.apply(r#move);
+                self.reroot(child);
+                true
+            }
+            _ => {
+                self.nodes.clear();
+                self.root_state = None;
+                false
+            }
+        }
+    }
+
+    /// Follows our last move and the opponent's reply from the memento down the tree. After
+    /// pondering the tree has already been moved along our move.
+    ///
+    /// returns: whether the tree now has the position at its root
+    fn reuse_tree(&mut self, gamestate: &Gamestate) -> bool {
+        let reply = match gamestate.last_move {
+            Some(reply) => reply,
+            None => return false,
+        };
+        if let Some(played) = self.played.take() {
+            if !self.advance(&played) {
+                return false;
+            }
+        }
+        if !self.advance(&reply) {
+            return false;
+        }
+
+        //The moves only key the tree, the position has to match as well
+        self.root_state
+            .is_some_and(|state| state.same_position(gamestate))
+    }
+
+    /// Makes the node the new root, dropping everything outside its subtree.
+    fn reroot(&mut self, root: usize) {
+        let mut old = std::mem::take(&mut self.nodes);
+        let mut pending = vec![(root, 0)];
+        self.nodes.push(Node {
+            children: Vec::new(),
+            ..old[root].clone()
+        });
+
+        while let Some((old_index, new_index)) = pending.pop() {
+            for child in std::mem::take(&mut old[old_index].children) {
+                let index = self.nodes.len();
+                self.nodes.push(Node {
+                    children: Vec::new(),
+                    ..old[child].clone()
+                });
+                self.nodes[new_index].children.push(index);
+                pending.push((child, index));
+            }
+        }
+    }
+
+    /// One round of selection, expansion, playout and backpropagation.
+    fn iterate(&mut self, root: &Gamestate) {
+        let mut gamestate = *root;
+        let mut path = vec![0];
+        let mut node = 0;
+
+        //Selection
+        while self.nodes[node].untried.is_empty() && !self.nodes[node].children.is_empty() {
+            let parent_visits = self.nodes[node].visits;
+            node = *self.nodes[node]
+                .children
+                .iter()
+                .max_by(|&&a, &&b| {
+                    let a = self.nodes[a].uct(parent_visits);
+                    let b = self.nodes[b].uct(parent_visits);
+                    a.total_cmp(&b)
+                })
+                .unwrap();
+            gamestate.apply(&self.nodes[node].r#move);
+            path.push(node);
+        }
+
+        //Expansion
+        if !self.nodes[node].untried.is_empty() {
+            let untried = &mut self.nodes[node].untried;
+            let index = self.rng.gen_range(0..untried.len());
+            let r#move = untried.swap_remove(index);
+
+            let team = gamestate.current_team();
+            gamestate.apply(&r#move);
+
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(r#move, team, &gamestate));
+            self.nodes[node].children.push(child);
+            path.push(child);
+        }
+
+        //Playout
+        let winner = self.playout(&mut gamestate);
+
+        //Backpropagation
+        for &node in path.iter() {
+            let node = &mut self.nodes[node];
+            node.visits += 1;
+            node.score += match winner {
+                None => 0.5,
+                Some(winner) if winner == node.team => 1.0,
+                Some(_) => 0.0,
+            };
+        }
+    }
+
+    /// Plays moves chosen by the playout policy until the game is over.
+    fn playout(&mut self, gamestate: &mut Gamestate) -> Option<Team> {
+        while !gamestate.is_over() {
+            let moves = gamestate.board.legal_moves();
+            let r#move = match self.playout {
+                Playout::Random => moves.choose(&mut self.rng).copied(),
+                Playout::Light => self.light_move(&gamestate.board, &moves),
+            };
+            match r#move {
+                Some(r#move) => gamestate.apply(&r#move),
+                None => break,
+            };
+        }
+        gamestate.winner()
+    }
+
+    fn light_move(&mut self, board: &Board, moves: &MoveList) -> Option<Move> {
+        if let Some(&r#move) = moves.iter().find(|r#move| board.ambers_of(r#move) > 0) {
+            return Some(r#move);
+        }
+
+        if self.rng.gen_bool(CAPTURE_PROBABILITY) {
+            let mut captures: MoveList = MoveList::new();
+            captures.extend(moves.iter().copied().filter(Move::is_capture));
+            if let Some(&capture) = captures.choose(&mut self.rng) {
+                return Some(capture);
+            }
+        }
+        moves.choose(&mut self.rng).copied()
+    }
+}
+
+impl Strategy for Mcts {
+    fn choose_move(&mut self, gamestate: &Gamestate, _: Team, budget: Duration) -> Option<Move> {
+        self.search(gamestate, budget)
+    }
+
+    fn ponder(&mut self, gamestate: &Gamestate, stop: Arc<AtomicBool>) {
+        Mcts::ponder(self, gamestate, stop);
+    }
+
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(seed: u64) -> Gamestate {
+        Gamestate::random_start(&mut StdRng::seed_from_u64(seed))
+    }
+
+    /// Checks that no node has more visits below it than it has itself.
+    fn assert_consistent(mcts: &Mcts) {
+        for node in mcts.nodes.iter() {
+            let below: u32 = node.children.iter().map(|&c| mcts.nodes[c].visits).sum();
+            assert!(below <= node.visits);
+        }
+    }
+
+    #[test]
+    fn unvisited_nodes_come_first() {
+        let gamestate = start(3);
+        let mut node = Node::new(gamestate.board.legal_moves()[0], Team::ONE, &gamestate);
+        assert_eq!(node.uct(0), f64::INFINITY);
+
+        node.visits = 2;
+        node.score = 1.5;
+        assert_eq!(node.uct(2), 0.75 + EXPLORATION * (2f64.ln() / 2.0).sqrt());
+    }
+
+    #[test]
+    fn reuses_the_tree_after_the_opponent_reply() {
+        let mut mcts = Mcts::with_seed(Playout::Light, 1);
+        let mut gamestate = start(18);
+
+        let played = mcts.search(&gamestate, Duration::from_millis(50)).unwrap();
+        let node = mcts.nodes[0]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| mcts.nodes[child].r#move == played)
+            .unwrap();
+        let reply = mcts.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|&c| mcts.nodes[c].visits)
+            .unwrap();
+        let (reply, visits) = (mcts.nodes[reply].r#move, mcts.nodes[reply].visits);
+
+        gamestate.apply(&played);
+        gamestate.apply(&reply);
+        gamestate.last_move = Some(reply);
+
+        mcts.search(&gamestate, Duration::from_millis(10)).unwrap();
+        assert!(mcts.nodes[0].visits > visits);
+        assert_eq!(mcts.root_state.unwrap().board, gamestate.board);
+        assert_consistent(&mcts);
+    }
+
+    #[test]
+    fn keeps_the_pondered_subtree_of_the_reply() {
+        let mut mcts = Mcts::with_seed(Playout::Light, 3);
+        let mut gamestate = start(21);
+
+        let played = mcts.search(&gamestate, Duration::from_millis(10)).unwrap();
+        gamestate.apply(&played);
+        gamestate.last_move = Some(played);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let signal = Arc::clone(&stop);
+        let stopper = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            signal.store(true, Ordering::Relaxed);
+        });
+        mcts.ponder(&gamestate, stop);
+        stopper.join().unwrap();
+        assert!(mcts.root_state.unwrap().same_position(&gamestate));
+        assert_consistent(&mcts);
+
+        let reply = mcts.nodes[0]
+            .children
+            .iter()
+            .copied()
+            .max_by_key(|&c| mcts.nodes[c].visits)
+            .unwrap();
+        let (reply, visits) = (mcts.nodes[reply].r#move, mcts.nodes[reply].visits);
+        gamestate.apply(&reply);
+        gamestate.last_move = Some(reply);
+
+        assert!(mcts.reuse_tree(&gamestate));
+        assert_eq!(mcts.nodes[0].visits, visits);
+    }
+
+    #[test]
+    fn drops_the_tree_for_another_position() {
+        let mut mcts = Mcts::with_seed(Playout::Random, 2);
+        let gamestate = start(19);
+        mcts.search(&gamestate, Duration::from_millis(20)).unwrap();
+
+        //A game the tree knows nothing about
+        let mut other = start(20);
+        let r#move = other.board.legal_moves()[0];
+        other.apply(&r#move);
+        other.last_move = Some(r#move);
+
+        assert!(!mcts.reuse_tree(&other));
+    }
+}
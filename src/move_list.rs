@@ -0,0 +1,115 @@
+use crate::game_move::Move;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+use std::ops::{Deref, DerefMut};
+
+/// Upper bound of the legal moves in any position: two pieces of each type, with eight
+/// Robbe, five Seestern, four Möwe and two Herzmuschel moves.
+pub const MAX_MOVES: usize = 40;
+
+/// A list of up to `N` moves, living on the stack. The default capacity holds the legal moves
+/// of any position; longer sequences of moves, like principal variations, need their own.
+///
+/// Dereferences to a slice of the stored moves, so it can be iterated, indexed and sorted in
+/// place like one.
+#[derive(Copy, Clone)]
+pub struct MoveList<const N: usize = MAX_MOVES> {
+    moves: [Move; N],
+    len: usize,
+}
+
+impl<const N: usize> MoveList<N> {
+    pub const fn new() -> Self {
+        MoveList {
+            moves: [Move::null(); N],
+            len: 0,
+        }
+    }
+
+    /// Appends a move to the list.
+    ///
+    /// Panics if the list is already full.
+    pub fn push(&mut self, r#move: Move) {
+        assert!(self.len < N, "Move list is full with {} moves", N);
+        self.moves[self.len] = r#move;
+        self.len += 1;
+    }
+
+    /// Removes the move at the index and returns it, replacing it with the last move.
+    pub fn swap_remove(&mut self, index: usize) -> Move {
+        let r#move = self[index];
+        self.len -= 1;
+        self.moves[index] = self.moves[self.len];
+        r#move
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends all moves of the slice to the list.
+    ///
+    /// Panics if they don't fit into the list.
+    pub fn extend_from_slice(&mut self, moves: &[Move]) {
+        assert!(
+            self.len + moves.len() <= N,
+            "{} moves don't fit into a move list of {} holding {}",
+            moves.len(),
+            N,
+            self.len
+        );
+        self.moves[self.len..self.len + moves.len()].copy_from_slice(moves);
+        self.len += moves.len();
+    }
+}
+
+impl<const N: usize> Default for MoveList<N> {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl<const N: usize> Deref for MoveList<N> {
+    type Target = [Move];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+impl<const N: usize> DerefMut for MoveList<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a MoveList<N> {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<const N: usize> Extend<Move> for MoveList<N> {
+    fn extend<T: IntoIterator<Item = Move>>(&mut self, iter: T) {
+        for r#move in iter {
+            self.push(r#move);
+        }
+    }
+}
+
+impl<const N: usize> PartialEq for MoveList<N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<const N: usize> Eq for MoveList<N> {}
+
+impl<const N: usize> Debug for MoveList<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,266 @@
+use crate::board::Board;
+use crate::game_move::Move;
+use crate::gamestate::Gamestate;
+use crate::piece::PieceType;
+use crate::position;
+use crate::team::Team;
+
+/// The starting position with team one lined up as `RHSMMSHR`.
+pub const START: &str = "R6r/H6h/S6s/M6m/M6m/S6s/H6h/R6r ONE 0 0 0";
+
+/// Writes the gamestate as a single line of the form
+/// `<board> <side to move> <turn> <ambers of team one> <ambers of team two>`.
+///
+/// The board lists the rows from `y = 7` down to `y = 0`, separated by `/`. Each row runs from
+/// `x = 0` to `x = 7`, with a digit standing for that many empty squares. Pieces are written
+/// as in [`PieceType::symbol`], upper case for team one and lower case for team two, and a
+/// tower is marked by a `*` after its letter.
+///
+/// # Examples
+///
+/// ```text
+/// let gamestate = parse(START)?;
+/// assert_eq!(write(&gamestate), START);
+/// ```
+pub fn write(gamestate: &Gamestate) -> String {
+    let board = &gamestate.board;
+    let mut rows = Vec::new();
+
+    for y in (0..8u8).rev() {
+        let mut row = String::new();
+        let mut empty = 0;
+
+        for x in 0..8u8 {
+            let pos = position!(x, y);
+            let (piece, team) = match (board.piece_at(pos), board.owner_at(pos)) {
+                (Some(piece), Some(team)) => (piece, team),
+                _ => {
+                    empty += 1;
+                    continue;
+                }
+            };
+
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+                empty = 0;
+            }
+            row.push(match team {
+                Team::ONE => piece.symbol(),
+                Team::TWO => piece.symbol().to_ascii_lowercase(),
+            });
+            if board.double_stack.get(pos) {
+                row.push('*');
+            }
+        }
+
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+        rows.push(row);
+    }
+
+    format!(
+        "{} {:?} {} {} {}",
+        rows.join("/"),
+        board.side_to_move,
+        gamestate.round,
+        gamestate.points.get_left(),
+        gamestate.points.get_right()
+    )
+}
+
+/// Parses a gamestate written by [`write`].
+pub fn parse(text: &str) -> Result<Gamestate, String> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() != 5 {
+        return Err(format!(
+            "Expected 5 fields in position '{}', found {}",
+            text,
+            fields.len()
+        ));
+    }
+
+    let mut gamestate = Gamestate::new();
+    gamestate.board = parse_board(fields[0])?;
+    gamestate.board.side_to_move = match fields[1] {
+        "ONE" => Team::ONE,
+        "TWO" => Team::TWO,
+        side => return Err(format!("Unknown side to move '{}'", side)),
+    };
+    gamestate.board.hash = gamestate.board.calculate_hash();
+
+    gamestate.round = parse_number(fields[2], "turn")?;
+    gamestate.set_ambers(Team::ONE, parse_ambers(fields[3])?);
+    gamestate.set_ambers(Team::TWO, parse_ambers(fields[4])?);
+
+    Ok(gamestate)
+}
+
+/// Writes a move as the symbol of its piece, the coordinates it moves from, `-` or `x` for a
+/// capture, and the coordinates it moves to, like `R03x14`.
+pub fn write_move(r#move: &Move) -> String {
+    let (origin, result) = (r#move.origin(), r#move.result());
+    format!(
+        "{}{}{}{}{}{}",
+        r#move.piece().symbol(),
+        origin.x,
+        origin.y,
+        if r#move.is_capture() { 'x' } else { '-' },
+        result.x,
+        result.y
+    )
+}
+
+/// Parses a move written by [`write_move`], which has to be legal in the position.
+pub fn parse_move(text: &str, gamestate: &Gamestate) -> Result<Move, String> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|digit| digit as u8)
+        .collect();
+    if text.len() != 6 || digits.len() != 4 || digits.iter().any(|&digit| digit > 7) {
+        return Err(format!("Invalid move '{}'", text));
+    }
+
+    let from = position!(digits[0], digits[1]);
+    let to = position!(digits[2], digits[3]);
+    gamestate
+        .board
+        .move_between(from, to)
+        .ok_or_else(|| format!("Move '{}' isn't legal in the position", text))
+}
+
+fn parse_board(text: &str) -> Result<Board, String> {
+    let rows: Vec<&str> = text.split('/').collect();
+    if rows.len() != 8 {
+        return Err(format!("Expected 8 rows in board '{}'", text));
+    }
+
+    let mut board = Board::new();
+    for (row, y) in rows.iter().zip((0..8u8).rev()) {
+        let mut x = 0u8;
+        let mut chars = row.chars().peekable();
+
+        while let Some(symbol) = chars.next() {
+            if let Some(empty) = symbol.to_digit(10) {
+                if empty == 0 || x + empty as u8 > 8 {
+                    return Err(format!("Row '{}' doesn't have 8 squares", row));
+                }
+                x += empty as u8;
+                continue;
+            }
+
+            let piece = PieceType::from_symbol(symbol)
+                .ok_or_else(|| format!("Unknown piece '{}' in row '{}'", symbol, row))?;
+            if x > 7 {
+                return Err(format!("Row '{}' is longer than 8 squares", row));
+            }
+
+            let pos = position!(x, y);
+            if symbol.is_ascii_uppercase() {
+                board.team_one.set(pos);
+            } else {
+                board.team_two.set(pos);
+            }
+            match piece {
+                PieceType::ROBBE => board.robben.set(pos),
+                PieceType::MUSCHEL => board.muscheln.set(pos),
+                PieceType::SEESTERN => board.seesterne.set(pos),
+                PieceType::MOEWE => board.moewen.set(pos),
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                board.double_stack.set(pos);
+            }
+            x += 1;
+        }
+
+        if x != 8 {
+            return Err(format!("Row '{}' doesn't have 8 squares", row));
+        }
+    }
+    Ok(board)
+}
+
+fn parse_number(text: &str, what: &str) -> Result<u8, String> {
+    text.parse::<u8>()
+        .map_err(|_| format!("Invalid {} '{}'", what, text))
+}
+
+/// Amber counts are stored in a nibble, so they can't exceed 15.
+fn parse_ambers(text: &str) -> Result<u8, String> {
+    match parse_number(text, "amber count")? {
+        ambers @ 0..=15 => Ok(ambers),
+        _ => Err(format!("Invalid amber count '{}'", text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn start_position() {
+        let gamestate = parse(START).unwrap();
+        assert_eq!(
+            gamestate.board,
+            Board::from_line_up(&[
+                PieceType::ROBBE,
+                PieceType::MUSCHEL,
+                PieceType::SEESTERN,
+                PieceType::MOEWE,
+                PieceType::MOEWE,
+                PieceType::SEESTERN,
+                PieceType::MUSCHEL,
+                PieceType::ROBBE,
+            ])
+        );
+        assert_eq!(write(&gamestate), START);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = StdRng::seed_from_u64(0xFE4);
+
+        for _ in 0..50 {
+            for gamestate in Gamestate::random_game(&mut rng) {
+                //The notation doesn't hold the last move
+                let gamestate = Gamestate {
+                    last_move: None,
+                    ..gamestate
+                };
+                let text = write(&gamestate);
+                assert_eq!(parse(&text).unwrap(), gamestate, "Round trip of '{}'", text);
+                for r#move in gamestate.board.legal_moves().iter() {
+                    assert_eq!(parse_move(&write_move(r#move), &gamestate), Ok(*r#move));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        for text in [
+            "99999999999999999999999999999/8/8/8/8/8/8/8 ONE 0 0 0",
+            "9/8/8/8/8/8/8/8 ONE 0 0 0",
+            "7R1/8/8/8/8/8/8/8 ONE 0 0 0",
+            "44R/8/8/8/8/8/8/8 ONE 0 0 0",
+            "08/8/8/8/8/8/8/8 ONE 0 0 0",
+            "R6/8/8/8/8/8/8/8 ONE 0 0 0",
+            "R7/8/8/8/8/8/8/8 THREE 0 0 0",
+            "R7/8/8/8/8/8/8/8 ONE 0 16 0",
+            "R7/8/8/8/8/8/8/8 ONE 0 0 300",
+            "R7/8/8/8/8/8/8 ONE 0 0 0",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+        assert_eq!(
+            parse("R7/8/8/8/8/8/8/8 ONE 0 15 0")
+                .unwrap()
+                .ambers(Team::ONE),
+            15
+        );
+    }
+}
@@ -0,0 +1,408 @@
+use crate::cli;
+use crate::cli::Flags;
+use crate::error::GameError;
+use crate::game_move::Move;
+use crate::game_result::GameResult;
+use crate::gamestate::Gamestate;
+use crate::match_runner::Engine;
+use crate::notation;
+use crate::team::Team;
+use crate::xml_node::XmlNode;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Read;
+use std::time::Duration;
+
+/// A game read from a replay file of the official server.
+///
+/// The file is a `<protocol>` document holding every state of the game, followed by the
+/// result. Recordings of the messages a client received, with each state wrapped in a memento
+/// inside a `<room>`, are read as well. Each state is checked against the one before it: the
+/// move leading to it has to be legal, and applying it with [`Board::apply`] has to give the
+/// same position.
+///
+/// [`Board::apply`]: crate::board::Board::apply
+#[derive(Debug)]
+pub struct OfficialReplay {
+    /// The states of the game, in the order they were sent
+    pub states: Vec<Gamestate>,
+    /// The move from each state to the next, if it could be found
+    pub moves: Vec<Option<Move>>,
+    /// Transitions that don't agree with our rules, by the turn they start from
+    pub mismatches: Vec<(u8, String)>,
+    /// The result, from the point of view of team one
+    pub result: Option<GameResult>,
+}
+
+/// The moves of a replay compared with the moves of an engine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// Number of positions in which the engine chose a move
+    pub compared: usize,
+    /// The positions in which it chose another move than the one played
+    pub disagreements: Vec<Disagreement>,
+}
+
+impl Analysis {
+    /// Number of positions in which the engine chose the move played.
+    pub fn agreements(&self) -> usize {
+        self.compared - self.disagreements.len()
+    }
+}
+
+/// A position in which the engine chose another move than the one played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disagreement {
+    pub turn: u8,
+    pub team: Team,
+    pub played: Move,
+    pub engine: Move,
+    /// Score of the engine's search, if it reports one
+    pub score: Option<f32>,
+}
+
+impl OfficialReplay {
+    pub fn load(path: &str) -> Result<Self, GameError> {
+        OfficialReplay::read(File::open(path)?)
+    }
+
+    /// Reads a replay document and rebuilds the moves of the game.
+    pub fn read<R: Read>(source: R) -> Result<Self, GameError> {
+        let root = XmlNode::read_document(source)?;
+
+        let mut state_nodes = Vec::new();
+        let mut result = None;
+        for node in root.children.iter() {
+            let data_nodes: Vec<&XmlNode> = match node.name.as_str() {
+                "state" => {
+                    state_nodes.push(node);
+                    continue;
+                }
+                "room" => node.children.iter().collect(),
+                "data" => vec![node],
+                _ => continue,
+            };
+            for data in data_nodes.into_iter().filter(|data| data.name == "data") {
+                match data.attribute("class")? {
+                    "memento" => state_nodes.push(data.required_child("state")?),
+                    "result" => result = Some(GameResult::from_node(data, Team::ONE)?),
+                    _ => {}
+                }
+            }
+        }
+        if state_nodes.is_empty() {
+            return Err(GameError::Protocol(String::from(
+                "The replay doesn't hold any state",
+            )));
+        }
+
+        let mut replay = OfficialReplay {
+            states: Vec::with_capacity(state_nodes.len()),
+            moves: Vec::new(),
+            mismatches: Vec::new(),
+            result,
+        };
+        for node in state_nodes {
+            let state = match replay.states.last().copied() {
+                Some(previous) => replay.follow(&previous, node)?,
+                None => Gamestate::try_from(node)?,
+            };
+            replay.states.push(state);
+        }
+        Ok(replay)
+    }
+
+    /// Reads the state following `previous`, recording the move between them and any
+    /// disagreement with our rules.
+    fn follow(&mut self, previous: &Gamestate, node: &XmlNode) -> Result<Gamestate, GameError> {
+        let state = match Gamestate::from_memento(node, previous) {
+            Ok(state) => state,
+            Err(GameError::Protocol(message)) => {
+                self.mismatches.push((previous.round, message));
+                self.moves.push(None);
+                return Gamestate::try_from(node);
+            }
+            Err(err) => return Err(err),
+        };
+
+        //Without a last move in the state, it is the legal move that leads to it
+        let r#move = state.last_move.or_else(|| {
+            previous.board.legal_moves().iter().copied().find(|r#move| {
+                let mut next = *previous;
+                next.apply(r#move);
+                next.same_position(&state)
+            })
+        });
+
+        match r#move {
+            Some(r#move) => {
+                let mut applied = *previous;
+                applied.apply(&r#move);
+                if !applied.same_position(&state) {
+                    self.mismatches.push((
+                        previous.round,
+                        format!(
+                            "{} leads to {}, but the replay continues with {}",
+                            notation::write_move(&r#move),
+                            notation::write(&applied),
+                            notation::write(&state)
+                        ),
+                    ));
+                }
+            }
+            None => self.mismatches.push((
+                previous.round,
+                format!("No legal move leads to {}", notation::write(&state)),
+            )),
+        }
+        self.moves.push(r#move);
+        Ok(state)
+    }
+
+    /// Lets the engine choose a move in every position of the given team, or of both teams,
+    /// and compares it with the move played.
+    pub fn analyze(&self, engine: &Engine, team: Option<Team>) -> Analysis {
+        let mut strategy = engine.strategy.build(&engine.options);
+        let mut analysis = Analysis {
+            compared: 0,
+            disagreements: Vec::new(),
+        };
+
+        for (state, played) in self.states.iter().zip(self.moves.iter()) {
+            let played = match played {
+                Some(played) => *played,
+                None => continue,
+            };
+            let mover = state.current_team();
+            if team.is_some_and(|team| team != mover) {
+                continue;
+            }
+
+            let choice = match strategy.choose_move(state, mover, engine.move_time) {
+                Some(choice) => choice,
+                None => continue,
+            };
+            analysis.compared += 1;
+            if choice != played {
+                analysis.disagreements.push(Disagreement {
+                    turn: state.round,
+                    team: mover,
+                    played,
+                    engine: choice,
+                    score: strategy.last_search().map(|info| info.score),
+                });
+            }
+        }
+        analysis
+    }
+}
+
+/// Entry point of the `replay` command: `replay <file> [--analyze] [--engine <engine>]
+/// [--team <team>]`. Checks every move of an official replay and, with `--analyze`, compares
+/// them to the moves of the engine.
+pub fn run(args: &[String]) {
+    let usage = "Usage: replay <file> [--analyze] [--engine <engine>] [--team <team>]";
+
+    let path = match args.first() {
+        Some(path) => path,
+        None => cli::exit_with_usage("Missing the replay file", usage),
+    };
+
+    let mut analyze = false;
+    let mut engine = Engine {
+        move_time: Duration::from_millis(500),
+        ..Engine::default()
+    };
+    let mut team = None;
+
+    let mut flags = Flags::new(&args[1..]);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--analyze" => {
+                analyze = true;
+                Ok(())
+            }
+            "--engine" => flags
+                .value()
+                .and_then(Engine::parse)
+                .map(|value| engine = value),
+            "--team" => flags.parse().map(|value| team = Some(value)),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, usage);
+        }
+    }
+
+    let replay = match OfficialReplay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            println!("Failed to read {}: {}", path, err);
+            return;
+        }
+    };
+
+    println!(
+        "{} states, {} moves",
+        replay.states.len(),
+        replay.moves.iter().flatten().count()
+    );
+    for (turn, mismatch) in replay.mismatches.iter() {
+        println!("Turn {:>2}: {}", turn, mismatch);
+    }
+    if replay.mismatches.is_empty() {
+        println!("Every move agrees with our rules");
+    }
+    if let Some(result) = &replay.result {
+        print!("{}", result);
+    }
+
+    if analyze {
+        println!("\nAnalyzing with {}", engine.name);
+        let analysis = replay.analyze(&engine, team);
+        for disagreement in analysis.disagreements.iter() {
+            println!(
+                "Turn {:>2} {:?}: played {}, engine prefers {}{}",
+                disagreement.turn,
+                disagreement.team,
+                notation::write_move(&disagreement.played),
+                notation::write_move(&disagreement.engine),
+                disagreement
+                    .score
+                    .map_or(String::new(), |score| format!(" (score {})", score))
+            );
+        }
+        println!(
+            "{} of {} moves agree with the engine",
+            analysis.agreements(),
+            analysis.compared
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_result::{Cause, TeamScore};
+    use crate::server::{write_memento, write_result};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const REPLAY: &str = include_str!("../res/replay.xml");
+
+    /// A replay of a random game as the server would write it.
+    fn random_replay(seed: u64, turns: usize) -> (Vec<Gamestate>, String) {
+        let mut states = Gamestate::random_game(&mut StdRng::seed_from_u64(seed));
+        states.truncate(turns + 1);
+
+        let xml = write_replay(&states);
+        (states, xml)
+    }
+
+    /// A replay of the states, ending in a draw, as the server would write it.
+    fn write_replay(states: &[Gamestate]) -> String {
+        let score = |team: Team| TeamScore {
+            name: format!("{:?}", team),
+            points: 1,
+            ambers: 0,
+            cause: Cause::Regular,
+        };
+        let result = GameResult::new([score(Team::ONE), score(Team::TWO)], None, Team::ONE);
+
+        let mut xml = String::from("<protocol>\n");
+        for state in states.iter() {
+            xml += &format!("  <room roomId=\"r\">{}</room>\n", write_memento(state));
+        }
+        xml += &format!(
+            "  <room roomId=\"r\">{}</room>\n</protocol>",
+            write_result(&result)
+        );
+        xml
+    }
+
+    #[test]
+    fn rebuilds_the_moves() {
+        let (states, xml) = random_replay(24, 20);
+        let replay = OfficialReplay::read(xml.as_bytes()).unwrap();
+
+        assert_eq!(replay.states.len(), states.len());
+        assert!(replay.mismatches.is_empty(), "{:?}", replay.mismatches);
+        for (r#move, state) in replay.moves.iter().zip(states[1..].iter()) {
+            assert_eq!(*r#move, state.last_move);
+        }
+        assert_eq!(replay.result.unwrap().winner, None);
+    }
+
+    #[test]
+    fn flags_impossible_transitions() {
+        let (_, xml) = random_replay(25, 4);
+        //Drops the memento after the first move, so the next one doesn't follow
+        let mut lines: Vec<&str> = xml.lines().collect();
+        lines.remove(2);
+        let replay = OfficialReplay::read(lines.join("\n").as_bytes()).unwrap();
+
+        assert_eq!(replay.mismatches.len(), 1);
+        assert_eq!(replay.mismatches[0].0, 0);
+        assert_eq!(replay.moves[0], None);
+    }
+
+    #[test]
+    fn fails_on_a_truncated_file() {
+        let (_, xml) = random_replay(26, 4);
+        let truncated = &xml[..xml.len() / 2];
+        assert!(OfficialReplay::read(truncated.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn reads_states_directly_under_the_protocol() {
+        let replay = OfficialReplay::read(REPLAY.as_bytes()).unwrap();
+
+        assert_eq!(replay.states.len(), 25);
+        assert!(replay.mismatches.is_empty(), "{:?}", replay.mismatches);
+        assert!(replay.moves.iter().all(Option::is_some));
+        let last = replay.states.last().unwrap();
+        assert!(last.is_over());
+        assert_eq!(last.ambers(Team::ONE), 2);
+        assert_eq!(replay.result.unwrap().winner, Some(Team::ONE));
+    }
+
+    #[test]
+    fn fails_without_states() {
+        assert!(OfficialReplay::read("<protocol></protocol>".as_bytes()).is_err());
+        let (_, xml) = random_replay(27, 4);
+        let results: Vec<&str> = xml
+            .lines()
+            .filter(|line| !line.contains("memento"))
+            .collect();
+        assert!(OfficialReplay::read(results.join("\n").as_bytes()).is_err());
+    }
+
+    #[test]
+    fn compares_the_engine_with_the_moves_played() {
+        //Team one scores with the Möwe on the base line, but later passes up a capture
+        let mut gamestate = notation::parse("r7/8/8/8/3Ms1M1/8/8/8 ONE 0 0 0").unwrap();
+        let mut states = vec![gamestate];
+        for r#move in ["M63-73", "R07-15", "M33-32", "R15-07"] {
+            let r#move = notation::parse_move(r#move, &gamestate).unwrap();
+            gamestate.apply(&r#move);
+            gamestate.last_move = Some(r#move);
+            states.push(gamestate);
+        }
+        let replay = OfficialReplay::read(write_replay(&states).as_bytes()).unwrap();
+        assert!(replay.mismatches.is_empty(), "{:?}", replay.mismatches);
+
+        //Among the moves of team one only the capture gains anything, so greedy is certain
+        let engine = Engine::parse("name=greedy,strategy=greedy").unwrap();
+        let analysis = replay.analyze(&engine, Some(Team::ONE));
+        assert_eq!(analysis.compared, 2);
+        assert_eq!(analysis.agreements(), 1);
+        assert_eq!(analysis.disagreements.len(), 1);
+
+        let disagreement = &analysis.disagreements[0];
+        assert_eq!(disagreement.turn, 2);
+        assert_eq!(disagreement.team, Team::ONE);
+        assert_eq!(notation::write_move(&disagreement.played), "M33-32");
+        assert_eq!(notation::write_move(&disagreement.engine), "M33x43");
+        assert_eq!(disagreement.score, None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::gamestate::Gamestate;
+use crate::notation;
+use std::time::Instant;
+
+/// Positions with their expected node counts, one [`PerftCase`] per line.
+const REGRESSION: &str = include_str!("../res/perft.txt");
+
+/// Number of whitespace separated fields of a position in the notation.
+const NOTATION_FIELDS: usize = 5;
+
+/// A position with the node counts perft is expected to find, starting at depth 1.
+pub struct PerftCase {
+    pub gamestate: Gamestate,
+    pub nodes: Vec<u64>,
+}
+
+impl PerftCase {
+    /// Parses a line of the form `<position> ; <nodes at depth 1> <nodes at depth 2> ...`,
+    /// with the position in the [`notation`].
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let (position, nodes) = line
+            .split_once(';')
+            .ok_or_else(|| format!("Missing ';' in perft case '{}'", line))?;
+
+        let gamestate = notation::parse(position)?;
+        let nodes = nodes
+            .split_whitespace()
+            .map(|count| {
+                count
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid node count '{}'", count))
+            })
+            .collect::<Result<Vec<u64>, String>>()?;
+
+        Ok(PerftCase { gamestate, nodes })
+    }
+}
+
+/// Parses a position in the [`notation`], which takes up the first five arguments, followed
+/// by moves played from there as read by [`notation::parse_move`].
+fn parse_position(args: &[String]) -> Result<Gamestate, String> {
+    if args.len() < NOTATION_FIELDS {
+        return Err(String::from("Missing position"));
+    }
+    let (position, moves) = args.split_at(NOTATION_FIELDS);
+
+    let mut gamestate = notation::parse(&position.join(" "))?;
+    for r#move in moves {
+        let r#move = notation::parse_move(r#move, &gamestate)?;
+        gamestate.apply(&r#move);
+    }
+    Ok(gamestate)
+}
+
+/// Entry point of the `perft` command: `perft <depth> <position> [moves...]`, with the
+/// position in the [`notation`]. Prints the node count below every legal move, followed by
+/// the total.
+pub fn run(args: &[String]) {
+    let usage = "Usage: perft <depth> <notation> [moves...]";
+
+    let depth = match args.first().map(|depth| depth.parse::<u8>()) {
+        Some(Ok(depth)) => depth,
+        _ => {
+            println!("{}", usage);
+            return;
+        }
+    };
+    let mut gamestate = match parse_position(&args[1..]) {
+        Ok(gamestate) => gamestate,
+        Err(err) => {
+            println!("{}\n{}", err, usage);
+            return;
+        }
+    };
+
+    println!("{}\n{}", gamestate, notation::write(&gamestate));
+
+    let start = Instant::now();
+    let divide = gamestate.divide(depth);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    for (r#move, nodes) in divide.iter() {
+        println!("{}: {}", notation::write_move(r#move), nodes);
+    }
+
+    let total: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    println!(
+        "\nMoves: {} | Nodes: {} | Time: {:.3}s",
+        divide.len(),
+        total,
+        elapsed
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regression_positions() {
+        for line in REGRESSION.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut case = PerftCase::parse(line).unwrap();
+            for (depth, expected) in case.nodes.iter().enumerate() {
+                assert_eq!(
+                    case.gamestate.perft(depth as u8 + 1),
+                    *expected,
+                    "Perft {} of '{}'",
+                    depth + 1,
+                    line
+                );
+            }
+        }
+    }
+}
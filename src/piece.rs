@@ -2,12 +2,17 @@
 
 use crate::bitboard::Bitboard;
 use crate::board::Board;
+use crate::error::GameError;
 use crate::game_move::Move;
+use crate::move_list::MoveList;
+use crate::position;
 use crate::vec2::Vec2;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use std::string::ParseError;
 
+/// The pieces and their move vectors. Vectors are given per team, indexed by
+/// [`Team::index`](crate::team::Team::index): team one moves forward along `+x`,
+/// team two along `-x`.
 pub mod pieces {
     use crate::piece::{Piece, PieceType};
     use crate::vec2::Vec2;
@@ -16,32 +21,56 @@ pub mod pieces {
         "Robbe",
         &PieceType::ROBBE,
         [
-            Vec2::new(2, 1),
-            Vec2::new(1, 2),
-            Vec2::new(-1, 2),
-            Vec2::new(-2, 1),
-            Vec2::new(-2, -1),
-            Vec2::new(-1, -2),
-            Vec2::new(1, -2),
-            Vec2::new(2, -1),
+            [
+                Vec2::new(2, 1),
+                Vec2::new(1, 2),
+                Vec2::new(-1, 2),
+                Vec2::new(-2, 1),
+                Vec2::new(-2, -1),
+                Vec2::new(-1, -2),
+                Vec2::new(1, -2),
+                Vec2::new(2, -1),
+            ],
+            [
+                Vec2::new(2, 1),
+                Vec2::new(1, 2),
+                Vec2::new(-1, 2),
+                Vec2::new(-2, 1),
+                Vec2::new(-2, -1),
+                Vec2::new(-1, -2),
+                Vec2::new(1, -2),
+                Vec2::new(2, -1),
+            ],
         ],
     );
 
     pub const MUSCHEL: Piece<2> = Piece::new(
         "Herzmuschel",
         &PieceType::MUSCHEL,
-        [Vec2::new(1, 1), Vec2::new(-1, 1)],
+        [
+            [Vec2::new(1, 1), Vec2::new(1, -1)],
+            [Vec2::new(-1, 1), Vec2::new(-1, -1)],
+        ],
     );
 
     pub const SEESTERN: Piece<5> = Piece::new(
         "Seestern",
         &PieceType::SEESTERN,
         [
-            Vec2::new(1, 1),
-            Vec2::new(0, 1),
-            Vec2::new(-1, 1),
-            Vec2::new(1, -1),
-            Vec2::new(-1, -1),
+            [
+                Vec2::new(1, 1),
+                Vec2::new(1, 0),
+                Vec2::new(1, -1),
+                Vec2::new(-1, 1),
+                Vec2::new(-1, -1),
+            ],
+            [
+                Vec2::new(-1, 1),
+                Vec2::new(-1, 0),
+                Vec2::new(-1, -1),
+                Vec2::new(1, 1),
+                Vec2::new(1, -1),
+            ],
         ],
     );
 
@@ -49,15 +78,46 @@ pub mod pieces {
         "Möwe",
         &PieceType::MOEWE,
         [
-            Vec2::new(1, 0),
-            Vec2::new(0, 1),
-            Vec2::new(-1, 0),
-            Vec2::new(0, -1),
+            [
+                Vec2::new(1, 0),
+                Vec2::new(0, 1),
+                Vec2::new(-1, 0),
+                Vec2::new(0, -1),
+            ],
+            [
+                Vec2::new(1, 0),
+                Vec2::new(0, 1),
+                Vec2::new(-1, 0),
+                Vec2::new(0, -1),
+            ],
         ],
     );
 }
 
-#[derive(Clone, Debug, Copy)]
+/// Destination bitboards of every piece on every square, precomputed from the move vectors
+/// in [`pieces`]. Indexed by [`Team::index`](crate::team::Team::index), then by position.
+pub mod destinations {
+    use crate::bitboard::Bitboard;
+    use crate::piece::pieces;
+    use crate::piece::PieceType;
+
+    pub static ROBBE: [[Bitboard; 64]; 2] = pieces::ROBBE.destinations();
+    pub static MUSCHEL: [[Bitboard; 64]; 2] = pieces::MUSCHEL.destinations();
+    pub static SEESTERN: [[Bitboard; 64]; 2] = pieces::SEESTERN.destinations();
+    pub static MOEWE: [[Bitboard; 64]; 2] = pieces::MOEWE.destinations();
+
+    /// The destination table of the given piece type.
+    pub fn of(piece: PieceType) -> &'static [[Bitboard; 64]; 2] {
+        match piece {
+            PieceType::ROBBE => &ROBBE,
+            PieceType::MUSCHEL => &MUSCHEL,
+            PieceType::SEESTERN => &SEESTERN,
+            PieceType::MOEWE => &MOEWE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum PieceType {
     ROBBE,
     MUSCHEL,
@@ -69,69 +129,127 @@ pub enum PieceType {
 pub struct Piece<const MOVE_COUNT: usize> {
     pub name: &'static str,
     pub typ: &'static PieceType,
-    pub vectors: [Vec2; MOVE_COUNT],
+    pub vectors: [[Vec2; MOVE_COUNT]; 2],
 }
 
 impl<const MOVE_COUNT: usize> Piece<MOVE_COUNT> {
     pub const fn new(
         name: &'static str,
         typ: &'static PieceType,
-        vectors: [Vec2; MOVE_COUNT],
+        vectors: [[Vec2; MOVE_COUNT]; 2],
     ) -> Self {
-        Piece {
-            name,
-            typ: &typ,
-            vectors,
+        Piece { name, typ, vectors }
+    }
+
+    /// Calculates the squares this piece can reach from every position, ignoring other pieces.
+    ///
+    /// returns: the destination bitboards, indexed by team and then by position
+    pub const fn destinations(&self) -> [[Bitboard; 64]; 2] {
+        let mut table = [[Bitboard::new(); 64]; 2];
+
+        let mut team = 0;
+        while team < 2 {
+            let mut pos = 0;
+            while pos < 64 {
+                let origin = Vec2::from_pos(pos as u8);
+                let mut bits = 0u64;
+
+                let mut i = 0;
+                while i < MOVE_COUNT {
+                    let x = origin.x + self.vectors[team][i].x;
+                    let y = origin.y + self.vectors[team][i].y;
+                    if x >= 0 && x < 8 && y >= 0 && y < 8 {
+                        bits |= 1 << position!(x, y);
+                    }
+                    i += 1;
+                }
+
+                table[team][pos] = Bitboard { bits };
+                pos += 1;
+            }
+            team += 1;
         }
+        table
     }
 
-    pub fn calculate_moves(&self, piece_positions: Bitboard, board: &Board) -> Vec<Move> {
-        let mut moves: Vec<Move> = Vec::new();
+    /// Calculates the moves of this piece standing at the given positions, for the team
+    /// whose turn it is, by trying every vector one by one. The moves are appended to `moves`.
+    ///
+    /// This is the reference for the table based [`Board::legal_moves`], which it is verified
+    /// and benchmarked against.
+    pub fn calculate_moves(&self, piece_positions: Bitboard, board: &Board, moves: &mut MoveList) {
+        let own = board.pieces(board.side_to_move);
+        let opponent = board.pieces(board.side_to_move.next());
+
         for position in piece_positions.get_set_bits() {
             let origin = Vec2::from_pos(position);
 
-            for vector in self.vectors.iter() {
-                let r#move = Move::new(origin, *vector, *self.typ);
+            for vector in self.vectors[board.side_to_move.index()].iter() {
+                let result = origin + *vector;
 
                 //Move not legal
-                if r#move.out_of_bounds()
-                    || board
-                        .friendly_pieces
-                        .get_at_coords(r#move.result.x as u8, r#move.result.y as u8)
+                if result.x > 7
+                    || result.y > 7
+                    || result.x < 0
+                    || result.y < 0
+                    || own.get_at_coords(result.x as u8, result.y as u8)
                 {
                     continue;
                 }
 
-                moves.push(r#move);
+                let target = position!(result.x, result.y);
+                moves.push(Move::new(position, target, *self.typ, opponent.get(target)));
             }
         }
-        moves
     }
 }
 
 impl PieceType {
-    pub fn piece_type_from_name(name: &String) -> Option<PieceType> {
-        return match name.as_str() {
+    /// The letter the piece is written as in boards and positions.
+    pub const fn symbol(&self) -> char {
+        match self {
+            PieceType::ROBBE => 'R',
+            PieceType::MUSCHEL => 'H',
+            PieceType::SEESTERN => 'S',
+            PieceType::MOEWE => 'M',
+        }
+    }
+
+    /// Inverse of [`PieceType::symbol`]. Lower case letters are accepted as well.
+    pub const fn from_symbol(symbol: char) -> Option<PieceType> {
+        match symbol.to_ascii_uppercase() {
+            'R' => Some(PieceType::ROBBE),
+            'H' => Some(PieceType::MUSCHEL),
+            'S' => Some(PieceType::SEESTERN),
+            'M' => Some(PieceType::MOEWE),
+            _ => None,
+        }
+    }
+
+    /// Light pieces earn an amber when reaching the opponent's base line. Only the Robbe isn't.
+    pub const fn is_light(&self) -> bool {
+        !matches!(self, PieceType::ROBBE)
+    }
+
+    /// The name of the piece type in the server's protocol, as read by
+    /// [`PieceType::piece_type_from_name`].
+    pub fn server_name(&self) -> &'static str {
+        match self {
+            PieceType::MOEWE => "Moewe",
+            PieceType::ROBBE => "Robbe",
+            PieceType::MUSCHEL => "Herzmuschel",
+            PieceType::SEESTERN => "Seestern",
+        }
+    }
+
+    pub fn piece_type_from_name(name: &str) -> Option<PieceType> {
+        match name {
             "Moewe" => Some(PieceType::MOEWE),
             "Robbe" => Some(PieceType::ROBBE),
             "Herzmuschel" => Some(PieceType::MUSCHEL),
             "Seestern" => Some(PieceType::SEESTERN),
             _ => None,
-        };
-    }
-}
-
-impl From<&String> for PieceType {
-    fn from(str: &String) -> Self {
-        return match str.as_str() {
-            "Moewe" => PieceType::MOEWE,
-            "Robbe" => PieceType::ROBBE,
-            "Herzmuschel" => PieceType::MUSCHEL,
-            "Seestern" => PieceType::SEESTERN,
-            piece => {
-                panic!("No piece of type: {}", piece)
-            }
-        };
+        }
     }
 }
 
@@ -155,17 +273,9 @@ impl Display for PieceType {
 }
 
 impl FromStr for PieceType {
-    type Err = ParseError;
+    type Err = GameError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "Moewe" => Ok(PieceType::MOEWE),
-            "Robbe" => Ok(PieceType::ROBBE),
-            "Herzmuschel" => Ok(PieceType::MUSCHEL),
-            "Seestern" => Ok(PieceType::SEESTERN),
-            piece => {
-                panic!("No piece of type: {}", piece)
-            }
-        }
+        PieceType::piece_type_from_name(s).ok_or_else(|| GameError::UnknownPiece(s.to_string()))
     }
 }
@@ -0,0 +1,187 @@
+use crate::game_move::Move;
+use crate::game_result::GameResult;
+use crate::gamestate::Gamestate;
+use crate::notation;
+use crate::search::SearchInfo;
+use crate::team::Team;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Writes a game to a replay file as it is played, one event per line:
+///
+/// ```text
+/// room <room id>
+/// team <our team>
+/// state <position>
+/// move <team> <move> <time in ms> [depth <n> score <score> nodes <n> pv <moves>]
+/// state <position>
+/// ...
+/// result <winning team or draw>
+/// score <team> <points> <ambers> <cause>
+/// name <team> <display name>
+/// ```
+///
+/// Positions are written with [`notation::write`] and moves with [`notation::write_move`].
+/// The time of our moves is the time spent choosing them, the time of the opponent's moves
+/// the time between the mementos of our move and theirs. Every line is flushed right away,
+/// so a crash doesn't lose the game so far.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    out: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Creates the replay file `<room id>.replay` in the directory.
+    pub fn create(
+        directory: &str,
+        room_id: &str,
+        team: Team,
+        start: &Gamestate,
+    ) -> io::Result<Self> {
+        //Room ids are UUIDs, but a file name must not be able to leave the directory
+        let name: String = room_id
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = Path::new(directory).join(format!("{}.replay", name));
+        log::info!("Recording the game to {}", path.display());
+
+        Recorder::new(BufWriter::new(File::create(path)?), room_id, team, start)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts the replay with the room, our team and the starting position.
+    pub fn new(out: W, room_id: &str, team: Team, start: &Gamestate) -> io::Result<Self> {
+        let mut recorder = Recorder { out };
+        recorder.line(format!("room {}", room_id))?;
+        recorder.line(format!("team {:?}", team))?;
+        recorder.record_state(start)?;
+        Ok(recorder)
+    }
+
+    pub fn record_state(&mut self, gamestate: &Gamestate) -> io::Result<()> {
+        self.line(format!("state {}", notation::write(gamestate)))
+    }
+
+    /// Records a move of the team, with our search info if it's our move.
+    pub fn record_move(
+        &mut self,
+        team: Team,
+        r#move: &Move,
+        time: Duration,
+        info: Option<&SearchInfo>,
+    ) -> io::Result<()> {
+        let mut line = format!(
+            "move {:?} {} {}",
+            team,
+            notation::write_move(r#move),
+            time.as_millis()
+        );
+        if let Some(info) = info {
+            let pv: Vec<String> = info.pv.iter().map(notation::write_move).collect();
+            line.push_str(&format!(
+                " depth {} score {} nodes {} pv {}",
+                info.depth,
+                info.score,
+                info.nodes,
+                pv.join(" ")
+            ));
+        }
+        self.line(line)
+    }
+
+    pub fn record_result(&mut self, result: &GameResult) -> io::Result<()> {
+        match result.winner {
+            Some(winner) => self.line(format!("result {:?}", winner))?,
+            None => self.line(String::from("result draw"))?,
+        }
+        for (team, score) in [Team::ONE, Team::TWO].iter().zip(result.scores.iter()) {
+            self.line(format!(
+                "score {:?} {} {} {}",
+                team, score.points, score.ambers, score.cause
+            ))?;
+            self.line(format!("name {:?} {}", team, score.name))?;
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: String) -> io::Result<()> {
+        writeln!(self.out, "{}", line)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_result::{Cause, TeamScore};
+    use crate::search::Line;
+
+    #[test]
+    fn records_a_game() {
+        let mut gamestate = notation::parse(notation::START).unwrap();
+        let mut recorder = Recorder::new(Vec::new(), "abc", Team::ONE, &gamestate).unwrap();
+
+        let ours = gamestate.board.legal_moves()[0];
+        let mut pv = Line::new();
+        pv.push(ours);
+        let info = SearchInfo {
+            best_move: Some(ours),
+            score: 0.25,
+            depth: 3,
+            pv,
+            nodes: 120,
+        };
+        recorder
+            .record_move(Team::ONE, &ours, Duration::from_millis(812), Some(&info))
+            .unwrap();
+        gamestate.apply(&ours);
+        recorder.record_state(&gamestate).unwrap();
+
+        let theirs = gamestate.board.legal_moves()[0];
+        recorder
+            .record_move(Team::TWO, &theirs, Duration::from_millis(40), None)
+            .unwrap();
+
+        let score = |ambers| TeamScore {
+            name: String::from("Team A"),
+            points: 1,
+            ambers,
+            cause: Cause::Regular,
+        };
+        let result = GameResult::new([score(0), score(0)], None, Team::ONE);
+        recorder.record_result(&result).unwrap();
+
+        let text = String::from_utf8(recorder.out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "room abc");
+        assert_eq!(lines[1], "team ONE");
+        assert_eq!(lines[2], format!("state {}", notation::START));
+        let ours = notation::write_move(&ours);
+        assert_eq!(
+            lines[3],
+            format!(
+                "move ONE {} 812 depth 3 score 0.25 nodes 120 pv {}",
+                ours, ours
+            )
+        );
+        assert!(lines[4].starts_with("state "));
+        assert_eq!(
+            lines[5],
+            format!("move TWO {} 40", notation::write_move(&theirs))
+        );
+        assert_eq!(lines[6], "result draw");
+        assert_eq!(lines[7], "score ONE 1 0 regular");
+        assert_eq!(lines[8], "name ONE Team A");
+    }
+}
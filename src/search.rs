@@ -0,0 +1,716 @@
+use crate::eval::EvalParams;
+use crate::game_move::Move;
+use crate::gamestate::Gamestate;
+use crate::move_list::MoveList;
+use crate::team::Team;
+use crate::time::Allocation;
+use crate::transposition::{Bound, TranspositionTable};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Score of a won position. Wins are reported as `WIN_SCORE - ply`, so that faster wins
+/// are preferred over slower ones.
+pub const WIN_SCORE: f32 = 1000.0;
+
+/// Search depth at which iterative deepening stops, even if there is time left.
+const MAX_DEPTH: u8 = 64;
+
+/// A line of play, long enough for the principal variation of the deepest search.
+pub type Line = MoveList<{ MAX_DEPTH as usize }>;
+
+/// The deadline is only checked every this many nodes, as reading the clock isn't free.
+const NODES_PER_TIME_CHECK: u64 = 1024;
+
+/// Outcome of a search.
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    /// The best move found, or `None` if the team to move has no legal moves
+    pub best_move: Option<Move>,
+    /// Score of the best move, from the point of view of the team to move
+    pub score: f32,
+    /// Depth the best move was searched to. An interrupted iteration counts once one of its
+    /// root moves is finished.
+    pub depth: u8,
+    /// The principal variation, starting with the best move
+    pub pv: Line,
+    /// Nodes visited over all iterations
+    pub nodes: u64,
+}
+
+/// Scores beyond this bound are wins or losses, whose distance to the root depends on the ply.
+const WIN_BOUND: f32 = WIN_SCORE - MAX_DEPTH as f32;
+
+/// Upper bound of pondering, which usually ends with the opponent's move long before.
+const PONDER_TIME: Duration = Duration::from_secs(60);
+
+/// Size of the transposition table as a power of two.
+pub const TT_BITS: u8 = 20;
+
+/// Largest transposition table that can be requested, as a power of two. With 24 bytes per
+/// entry it takes 1.5 GB, still leaving room on the contest machine.
+pub const MAX_TT_BITS: u8 = 26;
+
+/// Iterative deepening negamax search with alpha-beta pruning. The transposition table is
+/// kept between searches.
+///
+/// During the opponent's turn the searcher can ponder on the position after the reply it
+/// expects. If the opponent plays it, the next search finds the results of pondering in the
+/// transposition table.
+#[derive(Debug, Clone)]
+pub struct Searcher {
+    deadline: Instant,
+    nodes: u64,
+    stopped: bool,
+    tt: TranspositionTable,
+    /// Weights of the evaluation
+    pub params: EvalParams,
+    /// Set from another thread to end pondering
+    ponder_stop: Option<Arc<AtomicBool>>,
+    /// The reply to our last move in its principal variation
+    prediction: Option<Move>,
+    /// The position pondered on since the last search
+    pondered: Option<Gamestate>,
+    /// Result of the last search
+    last_search: Option<SearchInfo>,
+}
+
+impl Searcher {
+    pub fn new() -> Self {
+        Searcher::with_tt_bits(TT_BITS)
+    }
+
+    /// Creates a searcher whose transposition table holds `2^bits` entries.
+    pub fn with_tt_bits(bits: u8) -> Self {
+        Searcher {
+            deadline: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            tt: TranspositionTable::new(bits),
+            params: EvalParams::default(),
+            ponder_stop: None,
+            prediction: None,
+            pondered: None,
+            last_search: None,
+        }
+    }
+
+    /// Searches the position with increasing depth until the time allocated from the budget
+    /// is used up. Returns the result of the deepest completed iteration, or the best move of
+    /// an interrupted iteration if it already found a better one.
+    pub fn search(&mut self, gamestate: &Gamestate, budget: Duration) -> SearchInfo {
+        let allocation = Allocation::new(gamestate, budget);
+        self.deadline = allocation.deadline();
+
+        //On a ponder hit the entries of pondering stay in the current generation. Otherwise
+        //they belong to an older one and are the first to be replaced.
+        match self.pondered.take() {
+            Some(pondered) if pondered.same_position(gamestate) => log::info!("Ponder hit"),
+            Some(_) => {
+                log::info!("Ponder miss");
+                self.tt.new_search();
+            }
+            None => self.tt.new_search(),
+        }
+
+        let info = self.deepen(gamestate, |stable_iterations| {
+            allocation.next_iteration(stable_iterations)
+        });
+        self.prediction = info.pv.get(1).copied();
+        self.last_search = Some(info.clone());
+        info
+    }
+
+    /// The result of the last search, not counting pondering.
+    pub fn last_search(&self) -> Option<&SearchInfo> {
+        self.last_search.as_ref()
+    }
+
+    /// Searches the position after the expected reply of the opponent, whose turn it is,
+    /// until `stop` is set.
+    ///
+    /// returns: the position pondered on, if a reply could be predicted
+    pub fn ponder(&mut self, gamestate: &Gamestate, stop: Arc<AtomicBool>) -> Option<Gamestate> {
+        let legal = gamestate.board.legal_moves();
+        let reply = self
+            .prediction
+            .take()
+            .or_else(|| {
+                self.tt
+                    .probe(gamestate.hash())
+                    .and_then(|entry| entry.best_move)
+            })
+            .filter(|reply| legal.contains(reply))?;
+
+        let mut position = *gamestate;
+        position.apply(&reply);
+        position.last_move = Some(reply);
+        log::debug!("Pondering on {}", reply);
+
+        //Only the stop signal ends pondering
+        self.deadline = Instant::now() + PONDER_TIME;
+        self.ponder_stop = Some(stop);
+        self.tt.new_search();
+        let info = self.deepen(&position, |_| true);
+        self.ponder_stop = None;
+
+        log::debug!("Pondered to depth {} ({} nodes)", info.depth, info.nodes);
+        self.pondered = Some(position);
+        Some(position)
+    }
+
+    /// Iterative deepening, as long as `next_iteration` allows it given in how many iterations
+    /// in a row the best move stayed the same.
+    fn deepen(
+        &mut self,
+        gamestate: &Gamestate,
+        mut next_iteration: impl FnMut(u8) -> bool,
+    ) -> SearchInfo {
+        self.nodes = 0;
+        self.stopped = false;
+
+        let mut gamestate = *gamestate;
+        let mut info = SearchInfo {
+            best_move: gamestate.board.legal_moves().first().copied(),
+            score: 0.0,
+            depth: 0,
+            pv: Line::new(),
+            nodes: 0,
+        };
+        let mut stable_iterations = 0u8;
+
+        for depth in 1..=MAX_DEPTH {
+            let mut pv = Line::new();
+            let score = self.negamax(
+                &mut gamestate,
+                depth,
+                0,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                &info.pv,
+                &mut pv,
+            );
+
+            //The root moves an interrupted iteration did finish were searched with the full
+            //window, so one of them beating the previous best move is the better choice
+            if self.stopped {
+                if let Some(&best) = pv.first() {
+                    if info.best_move != Some(best) {
+                        log::debug!("Depth {} interrupted, switching to {}", depth, best);
+                    }
+                    info.best_move = Some(best);
+                    info.score = score;
+                    info.depth = depth;
+                    info.pv = pv;
+                }
+                break;
+            }
+
+            if !pv.is_empty() && pv.first() == info.pv.first() {
+                stable_iterations = stable_iterations.saturating_add(1);
+            } else {
+                stable_iterations = 0;
+            }
+            info.best_move = pv.first().copied().or(info.best_move);
+            info.score = score;
+            info.depth = depth;
+            info.pv = pv;
+
+            log::debug!(
+                "Depth {} | Score {} | Nodes {} | PV {:?}",
+                depth,
+                score,
+                self.nodes,
+                info.pv.iter().map(|m| m.to_string()).collect::<Vec<_>>()
+            );
+
+            //Nothing left to find once the outcome is known
+            if score.abs() >= WIN_BOUND || !next_iteration(stable_iterations) {
+                break;
+            }
+        }
+
+        info.nodes = self.nodes;
+        info
+    }
+
+    fn out_of_time(&self) -> bool {
+        Instant::now() >= self.deadline
+            || self
+                .ponder_stop
+                .as_ref()
+                .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    /// Negamax alpha-beta search. Returns the score of the position from the point of view of
+    /// the team to move and writes the principal variation into `pv`. `prev_pv` is the
+    /// principal variation of the previous iteration, whose moves are searched first.
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &mut self,
+        gamestate: &mut Gamestate,
+        depth: u8,
+        ply: u8,
+        mut alpha: f32,
+        beta: f32,
+        prev_pv: &[Move],
+        pv: &mut Line,
+    ) -> f32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_TIME_CHECK) && self.out_of_time() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return 0.0;
+        }
+
+        if gamestate.is_over() {
+            return terminal_score(gamestate, ply);
+        }
+        if depth == 0 {
+            return relative_eval(gamestate, &self.params);
+        }
+
+        let key = gamestate.hash();
+        let mut tt_move = None;
+
+        if let Some(entry) = self.tt.probe(key) {
+            tt_move = entry.best_move;
+
+            //The root always gets searched, so that there is a full principal variation
+            if ply > 0 && entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    pv.clear();
+                    pv.extend(entry.best_move);
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = gamestate.board.legal_moves();
+        if moves.is_empty() {
+            return relative_eval(gamestate, &self.params);
+        }
+        order_moves(gamestate, &mut moves, prev_pv.first(), tt_move.as_ref());
+
+        let original_alpha = alpha;
+        let mut best = f32::NEG_INFINITY;
+        let mut best_move = None;
+        let mut child_pv = Line::new();
+
+        for r#move in moves.iter() {
+            //Only follow the previous principal variation while we are still on it
+            let child_prev_pv = match prev_pv.first() {
+                Some(pv_move) if pv_move == r#move => &prev_pv[1..],
+                _ => &[],
+            };
+
+            child_pv.clear();
+            let undo = gamestate.make_move(r#move);
+            let score = -self.negamax(
+                gamestate,
+                depth - 1,
+                ply + 1,
+                -beta,
+                -alpha,
+                child_prev_pv,
+                &mut child_pv,
+            );
+            gamestate.unmake_move(undo);
+
+            //The best of the root moves searched so far still has its exact score
+            if self.stopped {
+                return if ply == 0 { best } else { 0.0 };
+            }
+
+            if score > best {
+                best = score;
+                best_move = Some(*r#move);
+                pv.clear();
+                pv.push(*r#move);
+                pv.extend_from_slice(&child_pv);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= original_alpha {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.tt
+            .store(key, best_move, score_to_tt(best, ply), depth, bound);
+
+        best
+    }
+}
+
+/// The evaluation from the point of view of the team to move.
+fn relative_eval(gamestate: &Gamestate, params: &EvalParams) -> f32 {
+    match gamestate.current_team() {
+        Team::ONE => gamestate.eval(params),
+        Team::TWO => -gamestate.eval(params),
+    }
+}
+
+/// Score of a finished game from the point of view of the team to move.
+fn terminal_score(gamestate: &Gamestate, ply: u8) -> f32 {
+    match gamestate.winner() {
+        None => 0.0,
+        Some(team) if team == gamestate.current_team() => WIN_SCORE - ply as f32,
+        Some(_) => -(WIN_SCORE - ply as f32),
+    }
+}
+
+/// Converts a score to be stored in the transposition table. Wins are stored relative to the
+/// node instead of the root, so they stay valid when the position is reached at another ply.
+fn score_to_tt(score: f32, ply: u8) -> f32 {
+    if score >= WIN_BOUND {
+        score + ply as f32
+    } else if score <= -WIN_BOUND {
+        score - ply as f32
+    } else {
+        score
+    }
+}
+
+/// Inverse of [`score_to_tt`].
+fn score_from_tt(score: f32, ply: u8) -> f32 {
+    if score >= WIN_BOUND {
+        score - ply as f32
+    } else if score <= -WIN_BOUND {
+        score + ply as f32
+    } else {
+        score
+    }
+}
+
+/// Sorts the moves so that the principal variation move comes first, then the best move
+/// stored in the transposition table, followed by captures of towers and then captures of
+/// single pieces.
+fn order_moves(
+    gamestate: &Gamestate,
+    moves: &mut [Move],
+    pv_move: Option<&Move>,
+    tt_move: Option<&Move>,
+) {
+    let board = &gamestate.board;
+
+    moves.sort_by_key(|r#move| {
+        if Some(r#move) == pv_move {
+            return 0;
+        }
+        if Some(r#move) == tt_move {
+            return 1;
+        }
+        if !r#move.is_capture() {
+            4
+        } else if board.double_stack.get(r#move.to()) {
+            2
+        } else {
+            3
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_runner::random_opening;
+    use crate::move_list::MAX_MOVES;
+    use crate::notation;
+    use crate::piece::PieceType;
+    use crate::position;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::thread;
+
+    const BUDGET: Duration = Duration::from_secs(2);
+
+    /// Team one holding an amber, with a Möwe at (x, 3) and a Robbe of team two in the
+    /// corner.
+    fn amber_ahead(x: u8) -> Gamestate {
+        let mut gamestate = Gamestate::new();
+        gamestate.board.team_one.set(position!(x, 3));
+        gamestate.board.moewen.set(position!(x, 3));
+        gamestate.board.team_two.set(position!(0, 0));
+        gamestate.board.robben.set(position!(0, 0));
+        gamestate.points.set_left(1);
+        gamestate
+    }
+
+    #[test]
+    fn finds_the_winning_amber() {
+        //The Möwe earns the second amber on the base line
+        for (x, ply) in [(6, 2), (5, 4)] {
+            let gamestate = amber_ahead(x);
+            let info = Searcher::new().search(&gamestate, BUDGET);
+
+            let winning_move = Move::new(
+                position!(x, 3),
+                position!(x + 1, 3),
+                PieceType::MOEWE,
+                false,
+            );
+            assert_eq!(info.best_move, Some(winning_move), "Möwe at x = {}", x);
+            assert_eq!(info.score, WIN_SCORE - ply as f32, "Möwe at x = {}", x);
+        }
+    }
+
+    /// Alpha-beta search to a fixed depth without a transposition table or move ordering.
+    fn plain_negamax(
+        gamestate: &mut Gamestate,
+        depth: u8,
+        ply: u8,
+        mut alpha: f32,
+        beta: f32,
+    ) -> f32 {
+        if gamestate.is_over() {
+            return terminal_score(gamestate, ply);
+        }
+        let moves = gamestate.board.legal_moves();
+        if depth == 0 || moves.is_empty() {
+            return relative_eval(gamestate, &EvalParams::default());
+        }
+
+        let mut best = f32::NEG_INFINITY;
+        for r#move in moves.iter() {
+            let undo = gamestate.make_move(r#move);
+            let score = -plain_negamax(gamestate, depth - 1, ply + 1, -beta, -alpha);
+            gamestate.unmake_move(undo);
+
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn transposition_table_keeps_the_result() {
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for _ in 0..4 {
+            let mut gamestate = random_opening(&mut rng, 6);
+            for depth in 1..=4 {
+                let mut searcher = Searcher::new();
+                searcher.deadline = Instant::now() + Duration::from_secs(600);
+                let mut pv = Line::new();
+                let score = searcher.negamax(
+                    &mut gamestate,
+                    depth,
+                    0,
+                    f32::NEG_INFINITY,
+                    f32::INFINITY,
+                    &[],
+                    &mut pv,
+                );
+
+                let (alpha, beta) = (f32::NEG_INFINITY, f32::INFINITY);
+                let expected = plain_negamax(&mut gamestate, depth, 0, alpha, beta);
+                assert_eq!(score, expected, "Depth {} of\n{}", depth, gamestate);
+
+                //Ties may be broken differently, but the best move has to score the same
+                let mut after = gamestate;
+                after.apply(&pv[0]);
+                let best = -plain_negamax(&mut after, depth - 1, 1, alpha, beta);
+                assert_eq!(
+                    best, expected,
+                    "{} at depth {} of\n{}",
+                    pv[0], depth, gamestate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn turn_limit_isnt_hidden_by_the_table() {
+        //With ambers tied the Möwe wins in two moves, but one round before the limit it can't
+        let early = notation::parse("8/8/8/8/5M2/8/8/r7 ONE 54 1 1").unwrap();
+        let late = notation::parse("8/8/8/8/5M2/8/8/r7 ONE 58 1 1").unwrap();
+        assert_ne!(early.hash(), late.hash());
+
+        let mut searcher = Searcher::with_tt_bits(16);
+        let info = searcher.search(&early, BUDGET);
+        assert_eq!(info.score, WIN_SCORE - 4.0);
+
+        //The deeper scores stored for the early position must not end the late search
+        let info = searcher.search(&late, BUDGET);
+        assert_eq!(info.score, 0.0);
+        assert_eq!(
+            info.score,
+            Searcher::with_tt_bits(16).search(&late, BUDGET).score
+        );
+    }
+
+    #[test]
+    fn converts_win_scores_for_the_table() {
+        for ply in [0, 1, 7, 30] {
+            for score in [0.0, 1.5, -3.25, WIN_SCORE - 9.0, -(WIN_SCORE - 12.0)] {
+                assert_eq!(score_from_tt(score_to_tt(score, ply), ply), score);
+            }
+        }
+
+        //A win in 4 found at ply 3 is a win in 1 from the stored node, and a win in 6 when
+        //the node is reached again at ply 5
+        let stored = score_to_tt(WIN_SCORE - 4.0, 3);
+        assert_eq!(stored, WIN_SCORE - 1.0);
+        assert_eq!(score_from_tt(stored, 5), WIN_SCORE - 6.0);
+        assert_eq!(score_from_tt(-stored, 5), -(WIN_SCORE - 6.0));
+        assert_eq!(score_to_tt(0.25, 10), 0.25);
+    }
+
+    #[test]
+    fn deep_principal_variation_of_a_sparse_position() {
+        //Two lone Robben can't score, so the search runs up to the end of the game, and the
+        //principal variations of its nodes get longer than the legal moves of any position
+        let mut gamestate = Gamestate::new();
+        gamestate.board.team_one.set(position!(0, 7));
+        gamestate.board.robben.set(position!(0, 7));
+        gamestate.board.team_two.set(position!(7, 0));
+        gamestate.board.robben.set(position!(7, 0));
+        let info = Searcher::new().search(&gamestate, BUDGET);
+
+        assert!(info.depth as usize > MAX_MOVES, "Depth {}", info.depth);
+        for r#move in info.pv.iter() {
+            assert!(gamestate.board.legal_moves().contains(r#move));
+            gamestate.apply(r#move);
+        }
+    }
+
+    /// Ponders on the position after the predicted reply to our move for a while.
+    fn ponder_for(searcher: &mut Searcher, gamestate: &Gamestate, time: Duration) -> Gamestate {
+        let stop = Arc::new(AtomicBool::new(false));
+        let signal = Arc::clone(&stop);
+        let timer = thread::spawn(move || {
+            thread::sleep(time);
+            signal.store(true, Ordering::Relaxed);
+        });
+        let pondered = searcher.ponder(gamestate, stop).unwrap();
+        timer.join().unwrap();
+        pondered
+    }
+
+    /// Searches to a fixed depth.
+    fn search_to_depth(searcher: &mut Searcher, gamestate: &Gamestate, depth: u8) -> SearchInfo {
+        searcher.deadline = Instant::now() + Duration::from_secs(600);
+        let mut iterations = 0;
+        let info = searcher.deepen(gamestate, |_| {
+            iterations += 1;
+            iterations < depth
+        });
+        assert_eq!(info.depth, depth);
+        info
+    }
+
+    #[test]
+    fn ponder_hit_keeps_the_work() {
+        let mut gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(20));
+        let mut searcher = Searcher::with_tt_bits(18);
+        let info = searcher.search(&gamestate, Duration::from_millis(300));
+        gamestate.apply(&info.best_move.unwrap());
+
+        let pondered = ponder_for(&mut searcher, &gamestate, Duration::from_millis(300));
+        let reply = pondered.last_move.unwrap();
+        if let Some(&predicted) = info.pv.get(1) {
+            assert_eq!(reply, predicted);
+        }
+        let mut expected = gamestate;
+        expected.apply(&reply);
+        assert!(pondered.same_position(&expected));
+
+        //The pondered entries reach the depth with less work than an empty table
+        let fresh = search_to_depth(&mut Searcher::with_tt_bits(18), &pondered, 5).nodes;
+        let warm = search_to_depth(&mut searcher.clone(), &pondered, 5).nodes;
+        assert!(
+            warm < fresh,
+            "{} nodes after pondering, {} without",
+            warm,
+            fresh
+        );
+
+        //A hit searches within the generation of pondering
+        let generation = searcher.tt.generation();
+        searcher.search(&pondered, Duration::from_millis(50));
+        assert_eq!(searcher.tt.generation(), generation);
+        assert!(searcher.pondered.is_none());
+    }
+
+    #[test]
+    fn ponder_miss_starts_a_new_generation() {
+        let mut gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(21));
+        let mut searcher = Searcher::with_tt_bits(16);
+        let info = searcher.search(&gamestate, Duration::from_millis(100));
+        gamestate.apply(&info.best_move.unwrap());
+
+        ponder_for(&mut searcher, &gamestate, Duration::from_millis(50));
+        let generation = searcher.tt.generation();
+
+        let reply = gamestate
+            .board
+            .legal_moves()
+            .iter()
+            .copied()
+            .find(|r#move| Some(*r#move) != info.pv.get(1).copied())
+            .unwrap();
+        gamestate.apply(&reply);
+        searcher.search(&gamestate, Duration::from_millis(50));
+        assert_eq!(searcher.tt.generation(), generation.wrapping_add(1));
+    }
+
+    #[test]
+    fn interrupted_iteration_reports_its_own_score() {
+        let gamestate = random_opening(&mut StdRng::seed_from_u64(4), 6);
+        let previous = search_to_depth(&mut Searcher::with_tt_bits(16), &gamestate, 2);
+
+        //With the deadline passed, the first time check stops the search at depth 3
+        let mut searcher = Searcher::with_tt_bits(16);
+        searcher.deadline = Instant::now();
+        let mut completed = 0;
+        let info = searcher.deepen(&gamestate, |_| {
+            completed += 1;
+            true
+        });
+        assert_eq!(completed, 2);
+        assert_ne!(info.best_move, previous.best_move);
+
+        let mut after = gamestate;
+        after.apply(&info.best_move.unwrap());
+        let (alpha, beta) = (f32::NEG_INFINITY, f32::INFINITY);
+        assert_eq!(info.depth, 3);
+        assert_eq!(info.score, -plain_negamax(&mut after, 2, 1, alpha, beta));
+    }
+
+    #[test]
+    fn principal_variation_is_legal() {
+        let mut gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(4));
+        let info = Searcher::with_tt_bits(16).search(&gamestate, Duration::from_millis(300));
+
+        assert!(info.depth > 0);
+        assert_eq!(info.pv.first().copied(), info.best_move);
+        for r#move in info.pv.iter() {
+            assert!(
+                gamestate.board.legal_moves().contains(r#move),
+                "{} isn't legal in\n{}",
+                r#move,
+                gamestate
+            );
+            gamestate.apply(r#move);
+        }
+    }
+}
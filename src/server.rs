@@ -0,0 +1,544 @@
+use crate::cli;
+use crate::cli::Flags;
+use crate::error::GameError;
+use crate::game_move::Move;
+use crate::game_result::{Cause, GameResult, TeamScore};
+use crate::gamestate;
+use crate::gamestate::Gamestate;
+use crate::team::Team;
+use crate::vec2::Vec2;
+use crate::xml_node::XmlNode;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+use xml::EventReader;
+
+/// A move taking longer than this loses the game, though the move is still received.
+pub const SOFT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A client not answering within this time loses the game without a move.
+pub const HARD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A stand-in for the game server, hosting one game after another between the next two
+/// clients to connect. It speaks the same protocol and judges moves with our own rules.
+pub struct Server {
+    listener: TcpListener,
+    rng: StdRng,
+    games: u32,
+    pub soft_timeout: Duration,
+    pub hard_timeout: Duration,
+}
+
+/// A connected client, reading from the stream it writes to.
+struct Player {
+    stream: TcpStream,
+    parser: EventReader<BufReader<TcpStream>>,
+}
+
+impl Player {
+    fn new(stream: TcpStream) -> Result<Self, GameError> {
+        Ok(Player {
+            parser: EventReader::new(BufReader::new(stream.try_clone()?)),
+            stream,
+        })
+    }
+
+    fn send(&mut self, message: &str) -> Result<(), GameError> {
+        self.stream.write_all(message.as_bytes())?;
+        self.stream.flush()?;
+        Ok(())
+    }
+
+    fn send_to_room(&mut self, room_id: &str, data: &str) -> Result<(), GameError> {
+        self.send(&format!("<room roomId=\"{}\">{}</room>", room_id, data))
+    }
+}
+
+impl Server {
+    /// Listens on the given address, shuffling the starting positions with the seed.
+    pub fn bind(address: &str, seed: u64) -> Result<Self, GameError> {
+        Ok(Server {
+            listener: TcpListener::bind(address)?,
+            rng: StdRng::seed_from_u64(seed),
+            games: 0,
+            soft_timeout: SOFT_TIMEOUT,
+            hard_timeout: HARD_TIMEOUT,
+        })
+    }
+
+    /// The port the server is listening on.
+    pub fn port(&self) -> Result<u16, GameError> {
+        Ok(self.listener.local_addr()?.port())
+    }
+
+    /// Waits for two clients to join and plays a game between them. The first client to
+    /// join plays team one.
+    ///
+    /// There is no limit on waiting for the clients to connect, so this blocks until the
+    /// second one does. A connected client has to send its join request within the hard
+    /// timeout though, and one that can't be written to anymore during the game has left it.
+    ///
+    /// returns: the result as sent to the clients, from the view of team one
+    pub fn host_game(&mut self) -> Result<GameResult, GameError> {
+        self.games += 1;
+        let room_id = format!("local-{}", self.games);
+
+        let mut players = [self.accept(&room_id)?, self.accept(&room_id)?];
+        let mut failure = None;
+        for (player, team) in players.iter_mut().zip([Team::ONE, Team::TWO]) {
+            let welcome = format!("<data class=\"welcomeMessage\" color=\"{:?}\"/>", team);
+            if let Err(err) = player.send_to_room(&room_id, &welcome) {
+                failure = failure.or(Some((team, Cause::Left(err.to_string()))));
+            }
+        }
+        log::info!("Started game {}", room_id);
+
+        let mut gamestate = Gamestate::random_start(&mut self.rng);
+
+        loop {
+            let memento = write_memento(&gamestate);
+            for (player, team) in players.iter_mut().zip([Team::ONE, Team::TWO]) {
+                if let Err(err) = player.send_to_room(&room_id, &memento) {
+                    failure = failure.or(Some((team, Cause::Left(err.to_string()))));
+                }
+            }
+
+            let team = gamestate.current_team();
+            if failure.is_some() || gamestate.is_over() || gamestate.board.legal_moves().is_empty()
+            {
+                break;
+            }
+
+            let player = &mut players[team.index()];
+            match self.request_move(player, &room_id, &gamestate) {
+                Ok(r#move) => {
+                    log::debug!("{:?}: {}", team, r#move);
+                    gamestate.apply(&r#move);
+                    gamestate.last_move = Some(r#move);
+                }
+                Err(cause) => failure = Some((team, cause)),
+            }
+        }
+
+        if let Some((team, cause)) = &failure {
+            log::info!("{:?} loses: {}", team, cause);
+        }
+        let result = judge(&gamestate, failure);
+        let message = write_result(&result);
+        for player in players.iter_mut() {
+            //A client that left can't be told anymore
+            if player.send_to_room(&room_id, &message).is_ok() {
+                player.send("</protocol>").ok();
+            }
+        }
+        Ok(result)
+    }
+
+    /// Accepts the next client and answers its join request. Clients that don't join within
+    /// the hard timeout are turned away.
+    fn accept(&self, room_id: &str) -> Result<Player, GameError> {
+        loop {
+            let (stream, address) = self.listener.accept()?;
+            stream.set_read_timeout(Some(self.hard_timeout))?;
+            let mut player = Player::new(stream)?;
+
+            match XmlNode::read_from(&mut player.parser) {
+                Ok(node) if matches!(node.name.as_str(), "join" | "joinRoom" | "joinPrepared") => {
+                    player.send(&format!("<protocol>\n<joined roomId=\"{}\"/>", room_id))?;
+                    log::info!("{} joined {}", address, room_id);
+                    return Ok(player);
+                }
+                Ok(node) => log::warn!("{} sent '{}' instead of joining", address, node.name),
+                Err(err) => log::warn!("{} failed to join: {}", address, err),
+            }
+        }
+    }
+
+    /// Asks the player for a move and waits for it, enforcing the timeouts and rules.
+    fn request_move(
+        &self,
+        player: &mut Player,
+        room_id: &str,
+        gamestate: &Gamestate,
+    ) -> Result<Move, Cause> {
+        let start = Instant::now();
+        player
+            .send_to_room(room_id, "<data class=\"moveRequest\"/>")
+            .map_err(|err| Cause::Left(err.to_string()))?;
+
+        loop {
+            let remaining = self
+                .hard_timeout
+                .checked_sub(start.elapsed())
+                .filter(|remaining| !remaining.is_zero())
+                .ok_or_else(hard_timeout)?;
+            player
+                .stream
+                .set_read_timeout(Some(remaining))
+                .map_err(|err| Cause::Left(err.to_string()))?;
+
+            let node = match XmlNode::read_from(&mut player.parser) {
+                Ok(node) => node,
+                Err(GameError::Io(err))
+                    if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    return Err(hard_timeout());
+                }
+                Err(GameError::Xml(err)) if is_timeout(&err) => return Err(hard_timeout()),
+                Err(GameError::Xml(err)) if is_disconnect(&err) => {
+                    return Err(Cause::Left(err.to_string()))
+                }
+                Err(err @ GameError::Xml(_)) => return Err(Cause::RuleViolation(err.to_string())),
+                Err(err) => return Err(Cause::Left(err.to_string())),
+            };
+
+            if node.name != "data" || node.attribute("class").ok() != Some("move") {
+                log::warn!("Ignoring '{}' while waiting for a move", node.name);
+                continue;
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed > self.soft_timeout {
+                return Err(Cause::SoftTimeout(format!(
+                    "Move took {} ms",
+                    elapsed.as_millis()
+                )));
+            }
+            return parse_move(&node, gamestate).map_err(Cause::RuleViolation);
+        }
+    }
+}
+
+/// Entry point of the `server` command: `server [--port <port>] [--games <n>] [--seed <n>]`.
+/// Hosts the given number of games, or games until stopped if it is 0.
+pub fn run(args: &[String]) {
+    let usage = "Usage: server [--port <port>] [--games <n>] [--seed <n>]";
+
+    let mut port = 13050u16;
+    let mut games = 0u32;
+    let mut seed = 0u64;
+
+    let mut flags = Flags::new(args);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--port" => flags.parse().map(|value| port = value),
+            "--games" => flags.parse().map(|value| games = value),
+            "--seed" => flags.parse().map(|value| seed = value),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, usage);
+        }
+    }
+
+    let mut server = match Server::bind(&format!("0.0.0.0:{}", port), seed) {
+        Ok(server) => server,
+        Err(err) => {
+            log::error!("Failed to start the server: {}", err);
+            return;
+        }
+    };
+    log::info!("Listening on port {}", port);
+
+    let mut played = 0;
+    while games == 0 || played < games {
+        match server.host_game() {
+            Ok(result) => log::info!("Game over\n{}", result),
+            Err(err) => log::error!("Game aborted: {}", err),
+        }
+        played += 1;
+    }
+}
+
+fn hard_timeout() -> Cause {
+    Cause::HardTimeout(String::from("No move received"))
+}
+
+/// Whether the XML error was caused by the read timeout of the stream.
+fn is_timeout(err: &xml::reader::Error) -> bool {
+    matches!(err.kind(), xml::reader::ErrorKind::Io(err)
+        if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut))
+}
+
+/// Whether the XML error was caused by the connection ending or breaking down.
+fn is_disconnect(err: &xml::reader::Error) -> bool {
+    matches!(
+        err.kind(),
+        xml::reader::ErrorKind::Io(_) | xml::reader::ErrorKind::UnexpectedEof
+    )
+}
+
+/// Reads the move of a `data` node of class `move`, which has to be legal in the position.
+fn parse_move(node: &XmlNode, gamestate: &Gamestate) -> Result<Move, String> {
+    let square = |name| {
+        node.required_child(name)
+            .and_then(gamestate::parse_square)
+            .map_err(|err| err.to_string())
+    };
+    let from = square("from")?;
+    let to = square("to")?;
+
+    gamestate.board.move_between(from, to).ok_or_else(|| {
+        format!(
+            "Illegal move from {} to {}",
+            Vec2::from_pos(from),
+            Vec2::from_pos(to)
+        )
+    })
+}
+
+/// Scores the finished game. A team that broke the rules or left loses, otherwise the ambers
+/// decide.
+fn judge(gamestate: &Gamestate, failure: Option<(Team, Cause)>) -> GameResult {
+    let (winner, causes) = match failure {
+        Some((team, cause)) => {
+            let mut causes = [Cause::Regular, Cause::Regular];
+            causes[team.index()] = cause;
+            (Some(team.next()), causes)
+        }
+        None => (gamestate.winner(), [Cause::Regular, Cause::Regular]),
+    };
+
+    let [cause_one, cause_two] = causes;
+    let score = |team: Team, cause: Cause| TeamScore {
+        name: format!("{:?}", team),
+        points: match winner {
+            None => 1,
+            Some(winner) if winner == team => 2,
+            Some(_) => 0,
+        },
+        ambers: gamestate.ambers(team),
+        cause,
+    };
+
+    GameResult::new(
+        [score(Team::ONE, cause_one), score(Team::TWO, cause_two)],
+        winner,
+        Team::ONE,
+    )
+}
+
+/// Writes the `data` node of a memento the way the server does.
+pub fn write_memento(gamestate: &Gamestate) -> String {
+    let board = &gamestate.board;
+    let mut pieces = String::new();
+    for pos in 0..64u8 {
+        if let (Some(piece), Some(team)) = (board.piece_at(pos), board.owner_at(pos)) {
+            let coords = Vec2::from_pos(pos);
+            let count = if board.double_stack.get(pos) { 2 } else { 1 };
+            pieces += &format!(
+                "<entry><coordinates x=\"{}\" y=\"{}\"/><piece type=\"{}\" team=\"{:?}\" count=\"{}\"/></entry>",
+                coords.x,
+                coords.y,
+                piece.server_name(),
+                team,
+                count
+            );
+        }
+    }
+
+    let last_move = match gamestate.last_move {
+        Some(r#move) => format!(
+            "<lastMove><from x=\"{}\" y=\"{}\"/><to x=\"{}\" y=\"{}\"/></lastMove>",
+            r#move.origin().x,
+            r#move.origin().y,
+            r#move.result().x,
+            r#move.result().y
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        "<data class=\"memento\"><state class=\"state\" turn=\"{}\"><startTeam>ONE</startTeam>\
+         <board><pieces>{}</pieces></board>{}<ambers>\
+         <entry><team>ONE</team><int>{}</int></entry>\
+         <entry><team>TWO</team><int>{}</int></entry></ambers></state></data>",
+        gamestate.round,
+        pieces,
+        last_move,
+        gamestate.ambers(Team::ONE),
+        gamestate.ambers(Team::TWO)
+    )
+}
+
+/// Writes the `data` node of a result the way the server does.
+pub fn write_result(result: &GameResult) -> String {
+    let mut scores = String::new();
+    for (team, score) in [Team::ONE, Team::TWO].iter().zip(result.scores.iter()) {
+        let (cause, reason) = match &score.cause {
+            Cause::Regular => ("REGULAR", ""),
+            Cause::Left(reason) => ("LEFT", reason.as_str()),
+            Cause::RuleViolation(reason) => ("RULE_VIOLATION", reason.as_str()),
+            Cause::SoftTimeout(reason) => ("SOFT_TIMEOUT", reason.as_str()),
+            Cause::HardTimeout(reason) => ("HARD_TIMEOUT", reason.as_str()),
+        };
+        scores += &format!(
+            "<entry><player name=\"{}\" team=\"{:?}\"/><score cause=\"{}\" reason=\"{}\">\
+             <part>{}</part><part>{}</part></score></entry>",
+            escape(&score.name),
+            team,
+            cause,
+            escape(reason),
+            score.points,
+            score.ambers
+        );
+    }
+
+    let winner = match result.winner {
+        Some(team) => format!("<winner team=\"{:?}\"/>", team),
+        None => String::new(),
+    };
+
+    format!(
+        "<data class=\"result\"><definition>\
+         <fragment name=\"Siegpunkte\"><aggregation>SUM</aggregation><relevantForRanking>true</relevantForRanking></fragment>\
+         <fragment name=\"Bernsteine\"><aggregation>AVERAGE</aggregation><relevantForRanking>true</relevantForRanking></fragment>\
+         </definition><scores>{}</scores>{}</data>",
+        scores, winner
+    )
+}
+
+/// Escapes text for use in an attribute value.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Join;
+    use crate::search::Searcher;
+    use std::thread;
+
+    /// Joins the server over a raw connection, as a client we don't control.
+    fn join(address: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"<protocol><join gameType=\"swc_2022_ostseeschach\"/>")
+            .unwrap();
+        stream
+    }
+
+    /// Reads the messages of the server up to the result, from the view of the team.
+    fn received_result(stream: TcpStream, team: Team) -> GameResult {
+        let mut parser = EventReader::new(BufReader::new(stream));
+        loop {
+            let node = XmlNode::read_from(&mut parser).unwrap();
+            if node.attribute("class").ok() == Some("result") {
+                return GameResult::from_node(&node, team).unwrap();
+            }
+        }
+    }
+
+    /// Hosts a game in which team one is played by `client` over a raw connection, while
+    /// team two only listens. Checks that team two receives the result of the server and
+    /// returns the cause given for team one.
+    fn cause_of_team_one(
+        mut server: Server,
+        client: impl FnOnce(TcpStream) + Send + 'static,
+    ) -> Cause {
+        let address = format!("127.0.0.1:{}", server.port().unwrap());
+        let host = thread::spawn(move || server.host_game().unwrap());
+
+        //The first client to connect plays team one
+        let one = join(&address);
+        let two = join(&address);
+        let player = thread::spawn(move || client(one));
+
+        let received = received_result(two, Team::TWO);
+        let result = host.join().unwrap();
+        player.join().unwrap();
+
+        assert_eq!(
+            received,
+            GameResult::new(result.scores.clone(), result.winner, Team::TWO)
+        );
+        assert_eq!(result.winner, Some(Team::TWO));
+        assert_eq!(result.scores[1].cause, Cause::Regular);
+        result.scores[0].cause.clone()
+    }
+
+    fn local_server() -> Server {
+        let mut server = Server::bind("127.0.0.1:0", 7).unwrap();
+        server.soft_timeout = Duration::from_millis(100);
+        server.hard_timeout = Duration::from_millis(400);
+        server
+    }
+
+    /// A move from the middle of the board, where no piece starts.
+    const ILLEGAL_MOVE: &[u8] = b"<room roomId=\"local-1\"><data class=\"move\">\
+        <from x=\"3\" y=\"3\"/><to x=\"4\" y=\"3\"/></data></room>";
+
+    #[test]
+    fn illegal_move_breaks_the_rules() {
+        let cause = cause_of_team_one(local_server(), |mut stream| {
+            stream.write_all(ILLEGAL_MOVE).unwrap();
+            //Stays until the server is done with the game
+            stream.read_to_end(&mut Vec::new()).ok();
+        });
+        assert!(matches!(cause, Cause::RuleViolation(_)), "{:?}", cause);
+    }
+
+    #[test]
+    fn late_move_is_a_soft_timeout() {
+        //The timeout is judged before the move, which therefore doesn't need to be legal
+        let cause = cause_of_team_one(local_server(), |mut stream| {
+            thread::sleep(Duration::from_millis(200));
+            stream.write_all(ILLEGAL_MOVE).unwrap();
+            stream.read_to_end(&mut Vec::new()).ok();
+        });
+        assert!(matches!(cause, Cause::SoftTimeout(_)), "{:?}", cause);
+    }
+
+    #[test]
+    fn missing_move_is_a_hard_timeout() {
+        let cause = cause_of_team_one(local_server(), |mut stream| {
+            stream.read_to_end(&mut Vec::new()).ok();
+        });
+        assert!(matches!(cause, Cause::HardTimeout(_)), "{:?}", cause);
+    }
+
+    #[test]
+    fn closed_connection_leaves_the_game() {
+        let cause = cause_of_team_one(local_server(), drop);
+        assert!(matches!(cause, Cause::Left(_)), "{:?}", cause);
+    }
+
+    /// Plays a full game between two of our clients over the local network.
+    #[test]
+    fn hosts_a_game_between_two_clients() {
+        let mut server = Server::bind("127.0.0.1:0", 7).unwrap();
+        let address = format!("127.0.0.1:{}", server.port().unwrap());
+        let host = thread::spawn(move || server.host_game().unwrap());
+
+        let clients: Vec<_> = (0..2)
+            .map(|_| {
+                let address = address.clone();
+                thread::spawn(move || {
+                    let strategy = Box::new(Searcher::with_tt_bits(16));
+                    let mut game = Join::ANY.connect(&address, strategy).unwrap();
+                    game.time.limit = Duration::from_millis(5);
+                    game.time.margin = Duration::ZERO;
+                    (game.team, game.game_loop().unwrap())
+                })
+            })
+            .collect();
+
+        let result = host.join().unwrap();
+        for client in clients {
+            let (team, received) = client.join().unwrap();
+            assert_eq!(
+                received,
+                GameResult::new(result.scores.clone(), result.winner, team)
+            );
+        }
+        for score in result.scores.iter() {
+            assert_eq!(score.cause, Cause::Regular);
+        }
+    }
+}
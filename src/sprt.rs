@@ -0,0 +1,399 @@
+use crate::cli;
+use crate::cli::Flags;
+use crate::match_runner;
+use crate::match_runner::{Engine, GameRecord, Tally};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::process;
+
+/// Exit status of the `sprt` command when H0 is accepted.
+pub const EXIT_H0: i32 = 3;
+
+/// Exit status of the `sprt` command when the game limit is reached without a decision.
+pub const EXIT_UNDECIDED: i32 = 4;
+
+/// A sequential probability ratio test of the hypothesis that the first engine is `elo1`
+/// stronger than the second against the hypothesis that it is `elo0` stronger.
+///
+/// `alpha` is the chance to accept a change that is no better than `elo0`, `beta` the chance
+/// to reject one that is as good as `elo1`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+/// The outcome of an SPRT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The first engine is at least `elo1` stronger
+    AcceptH1,
+    /// The first engine is at most `elo0` stronger
+    AcceptH0,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Checks that the error chances are probabilities and that the hypotheses differ, as the
+    /// bounds are meaningless otherwise.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, chance) in [("alpha", self.alpha), ("beta", self.beta)] {
+            if !(chance > 0.0 && chance < 1.0) {
+                return Err(format!("{} must lie between 0 and 1, got {}", name, chance));
+            }
+        }
+        if self.alpha + self.beta >= 1.0 {
+            return Err(String::from("alpha and beta must add up to less than 1"));
+        }
+        if self.elo0.partial_cmp(&self.elo1) != Some(Ordering::Less) {
+            return Err(format!(
+                "elo0 must be below elo1, got {} and {}",
+                self.elo0, self.elo1
+            ));
+        }
+        Ok(())
+    }
+
+    /// The lower and upper bound of the log-likelihood ratio, at which the test accepts H0
+    /// and H1 respectively.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// The log-likelihood ratio of the results, using the normal approximation of the
+    /// trinomial distribution of wins, draws and losses.
+    pub fn llr(&self, tally: &Tally) -> f64 {
+        let games = tally.games() as f64;
+        let score = tally.score();
+        let deviation = |result: f64, count: u32| count as f64 * (result - score).powi(2);
+        let variance = (deviation(1.0, tally.wins)
+            + deviation(0.5, tally.draws)
+            + deviation(0.0, tally.losses))
+            / games;
+        //All games ended the same, wait for more
+        if variance <= 0.0 {
+            return 0.0;
+        }
+
+        let s0 = expected_score(self.elo0);
+        let s1 = expected_score(self.elo1);
+        (s1 - s0) * (2.0 * score - s0 - s1) / (2.0 * variance / games)
+    }
+
+    /// Decides the test, or returns `None` if more games are needed.
+    pub fn decision(&self, tally: &Tally) -> Option<Decision> {
+        let (lower, upper) = self.bounds();
+        let llr = self.llr(tally);
+
+        if llr >= upper {
+            Some(Decision::AcceptH1)
+        } else if llr <= lower {
+            Some(Decision::AcceptH0)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::AcceptH1 => write!(f, "H1 accepted"),
+            Decision::AcceptH0 => write!(f, "H0 accepted"),
+        }
+    }
+}
+
+/// The expected score of an engine that is `elo` stronger than its opponent.
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Writes the state of a test as a JSON object.
+pub fn to_json(
+    engines: [&Engine; 2],
+    sprt: &Sprt,
+    tally: &Tally,
+    decision: Option<Decision>,
+    records: &[GameRecord],
+) -> String {
+    let (lower, upper) = sprt.bounds();
+    let (elo, margin) = tally.elo();
+    let result = match decision {
+        Some(Decision::AcceptH1) => "\"H1\"",
+        Some(Decision::AcceptH0) => "\"H0\"",
+        None => "null",
+    };
+    let games: Vec<String> = records
+        .iter()
+        .map(|record| {
+            format!(
+                "{{\"first\": \"{:?}\", \"winner\": {}, \"ambers\": [{}, {}], \"turns\": {}}}",
+                record.first,
+                match record.winner {
+                    Some(team) => format!("\"{:?}\"", team),
+                    None => String::from("null"),
+                },
+                record.ambers[0],
+                record.ambers[1],
+                record.turns
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\n  \"engines\": [{}, {}],\n  \"elo0\": {},\n  \"elo1\": {},\n  \"alpha\": {},\n  \
+         \"beta\": {},\n  \"wins\": {},\n  \"draws\": {},\n  \"losses\": {},\n  \"llr\": {},\n  \
+         \"bounds\": [{}, {}],\n  \"result\": {},\n  \"elo\": {},\n  \"elo_margin\": {},\n  \
+         \"games\": [\n    {}\n  ]\n}}\n",
+        json_string(&engines[0].name),
+        json_string(&engines[1].name),
+        sprt.elo0,
+        sprt.elo1,
+        sprt.alpha,
+        sprt.beta,
+        tally.wins,
+        tally.draws,
+        tally.losses,
+        json_number(sprt.llr(tally)),
+        json_number(lower),
+        json_number(upper),
+        result,
+        json_number(elo),
+        json_number(margin),
+        games.join(",\n    ")
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// JSON has no infinities, which the Elo difference takes on while one side hasn't scored.
+fn json_number(number: f64) -> String {
+    if number.is_finite() {
+        format!("{:.3}", number)
+    } else {
+        String::from("null")
+    }
+}
+
+/// Entry point of the `sprt` command: `sprt <engine> <engine> [options]`, with the engines
+/// as described in [`Engine::parse`]. Plays until the test is decided or the game limit is
+/// reached, printing the LLR after every game.
+///
+/// Exits with status 0 if H1 is accepted, [`EXIT_H0`] if H0 is accepted and
+/// [`EXIT_UNDECIDED`] without a decision, so that a script can gate a change on it. An
+/// invalid command line exits with status 2 and a failure to write the output with 1.
+pub fn run(args: &[String]) {
+    let usage = "Usage: sprt <engine> <engine> [--elo0 <elo>] [--elo1 <elo>] [--alpha <p>] \
+                 [--beta <p>] [--max-games <n>] [--threads <n>] [--seed <n>] [--output <file>]";
+
+    let engines = match match_runner::parse_engines(args) {
+        Ok(engines) => engines,
+        Err(err) => cli::exit_with_usage(&err, usage),
+    };
+
+    let mut sprt = Sprt::default();
+    let mut max_games = 10_000u32;
+    let mut threads = 1usize;
+    let mut seed = 0u64;
+    let mut output = None;
+
+    let mut flags = Flags::new(&args[2..]);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--elo0" => flags.parse().map(|value| sprt.elo0 = value),
+            "--elo1" => flags.parse().map(|value| sprt.elo1 = value),
+            "--alpha" => flags.parse().map(|value| sprt.alpha = value),
+            "--beta" => flags.parse().map(|value| sprt.beta = value),
+            "--max-games" => flags.parse().map(|value| max_games = value),
+            "--threads" => flags.parse().map(|value| threads = value),
+            "--seed" => flags.parse().map(|value| seed = value),
+            "--output" => flags.value().map(|value| output = Some(value.to_string())),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, usage);
+        }
+    }
+
+    if let Err(err) = sprt.validate() {
+        cli::exit_with_usage(&err, usage);
+    }
+
+    let (lower, upper) = sprt.bounds();
+    println!(
+        "SPRT {} vs {}: elo0 {} elo1 {} alpha {} beta {} | Bounds ({:.2}, {:.2})",
+        engines[0].name, engines[1].name, sprt.elo0, sprt.elo1, sprt.alpha, sprt.beta, lower, upper
+    );
+
+    let mut tally = Tally::default();
+    let mut decision = None;
+    let records = match_runner::play_match(
+        [&engines[0], &engines[1]],
+        max_games,
+        threads,
+        seed,
+        |record| {
+            tally.add(record);
+            decision = sprt.decision(&tally);
+            println!(
+                "Game {:>5}: W/D/L {}/{}/{} | LLR {:.2} ({:.2}, {:.2})",
+                tally.games(),
+                tally.wins,
+                tally.draws,
+                tally.losses,
+                sprt.llr(&tally),
+                lower,
+                upper
+            );
+            decision.is_none()
+        },
+    );
+
+    let (elo, margin) = tally.elo();
+    match decision {
+        Some(decision) => println!("\n{} after {} games", decision, tally.games()),
+        None => println!("\nNo decision after {} games", tally.games()),
+    }
+    println!("Elo {:+.1} ± {:.1}", elo, margin);
+
+    if let Some(path) = output {
+        let json = to_json(
+            [&engines[0], &engines[1]],
+            &sprt,
+            &tally,
+            decision,
+            &records,
+        );
+        match fs::write(&path, json) {
+            Ok(()) => println!("Wrote results to {}", path),
+            Err(err) => {
+                eprintln!("Failed to write {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    match decision {
+        Some(Decision::AcceptH1) => {}
+        Some(Decision::AcceptH0) => process::exit(EXIT_H0),
+        None => process::exit(EXIT_UNDECIDED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_and_decisions() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.944).abs() < 0.001 && (upper - 2.944).abs() < 0.001);
+
+        let strong = Tally {
+            wins: 300,
+            draws: 400,
+            losses: 200,
+        };
+        assert!(sprt.llr(&strong) > upper);
+        assert_eq!(sprt.decision(&strong), Some(Decision::AcceptH1));
+
+        let equal = Tally {
+            wins: 3000,
+            draws: 4000,
+            losses: 3000,
+        };
+        assert!(sprt.llr(&equal) < lower);
+        assert_eq!(sprt.decision(&equal), Some(Decision::AcceptH0));
+
+        let early = Tally {
+            wins: 3,
+            draws: 4,
+            losses: 2,
+        };
+        assert_eq!(sprt.decision(&early), None);
+    }
+
+    #[test]
+    fn validates_the_parameters() {
+        assert_eq!(Sprt::default().validate(), Ok(()));
+
+        let invalid = [
+            Sprt {
+                alpha: 0.0,
+                ..Sprt::default()
+            },
+            Sprt {
+                beta: 1.0,
+                ..Sprt::default()
+            },
+            Sprt {
+                alpha: f64::NAN,
+                ..Sprt::default()
+            },
+            Sprt {
+                alpha: 0.5,
+                beta: 0.6,
+                ..Sprt::default()
+            },
+            Sprt {
+                elo0: 10.0,
+                elo1: 10.0,
+                ..Sprt::default()
+            },
+            Sprt {
+                elo0: 5.0,
+                elo1: -5.0,
+                ..Sprt::default()
+            },
+        ];
+        for sprt in invalid.iter() {
+            assert!(sprt.validate().is_err(), "{:?}", sprt);
+        }
+    }
+
+    #[test]
+    fn json_output() {
+        let engine = Engine::parse("name=a \"quoted\" engine").unwrap();
+        let tally = Tally {
+            wins: 1,
+            draws: 0,
+            losses: 0,
+        };
+        let json = to_json([&engine, &engine], &Sprt::default(), &tally, None, &[]);
+
+        assert!(json.contains(r#""engines": ["a \"quoted\" engine", "a \"quoted\" engine"]"#));
+        assert!(json.contains("\"result\": null"));
+        assert!(json.contains("\"elo\": null"));
+    }
+}
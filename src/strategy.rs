@@ -0,0 +1,221 @@
+use crate::eval::EvalParams;
+use crate::game_move::Move;
+use crate::gamestate::Gamestate;
+use crate::mcts::{Mcts, Playout};
+use crate::search::{SearchInfo, Searcher, TT_BITS};
+use crate::team::Team;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A way of choosing moves, so that the client can play with any of them.
+pub trait Strategy: Send {
+    /// Chooses a move for `team`, whose turn it is, taking at most `budget` to do so.
+    ///
+    /// returns: the chosen move, or `None` if the team has no legal moves
+    fn choose_move(&mut self, gamestate: &Gamestate, team: Team, budget: Duration) -> Option<Move>;
+
+    /// Thinks about the position during the opponent's turn until `stop` is set, to choose
+    /// the next move faster or better. Does nothing by default.
+    fn ponder(&mut self, _gamestate: &Gamestate, _stop: Arc<AtomicBool>) {}
+
+    /// Details of the search behind the last chosen move, for strategies that search.
+    fn last_search(&self) -> Option<&SearchInfo> {
+        None
+    }
+
+    fn name(&self) -> &'static str;
+}
+
+/// The strategies that can be picked on the command line.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StrategyKind {
+    Random,
+    Greedy,
+    AlphaBeta,
+    Mcts,
+}
+
+/// Settings of the strategies, each using the ones that concern it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrategyOptions {
+    /// Size of the transposition table of the alpha-beta search as a power of two
+    pub tt_bits: u8,
+    /// Playout policy of the MCTS
+    pub playout: Playout,
+    /// Evaluation weights of the alpha-beta search
+    pub params: EvalParams,
+}
+
+impl Default for StrategyOptions {
+    fn default() -> Self {
+        StrategyOptions {
+            tt_bits: TT_BITS,
+            playout: Playout::Light,
+            params: EvalParams::default(),
+        }
+    }
+}
+
+impl StrategyKind {
+    pub fn build(&self, options: &StrategyOptions) -> Box<dyn Strategy> {
+        match self {
+            StrategyKind::Random => Box::new(RandomStrategy::new()),
+            StrategyKind::Greedy => Box::new(GreedyStrategy::new()),
+            StrategyKind::AlphaBeta => {
+                let mut searcher = Searcher::with_tt_bits(options.tt_bits);
+                searcher.params = options.params;
+                Box::new(searcher)
+            }
+            StrategyKind::Mcts => Box::new(Mcts::new(options.playout)),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(StrategyKind::Random),
+            "greedy" => Ok(StrategyKind::Greedy),
+            "alphabeta" => Ok(StrategyKind::AlphaBeta),
+            "mcts" => Ok(StrategyKind::Mcts),
+            s => Err(format!("No strategy named '{}'", s)),
+        }
+    }
+}
+
+impl Display for StrategyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyKind::Random => write!(f, "random"),
+            StrategyKind::Greedy => write!(f, "greedy"),
+            StrategyKind::AlphaBeta => write!(f, "alphabeta"),
+            StrategyKind::Mcts => write!(f, "mcts"),
+        }
+    }
+}
+
+/// Plays a random legal move.
+#[derive(Debug, Clone)]
+pub struct RandomStrategy {
+    rng: StdRng,
+}
+
+impl RandomStrategy {
+    pub fn new() -> Self {
+        RandomStrategy {
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn choose_move(&mut self, gamestate: &Gamestate, _: Team, _: Duration) -> Option<Move> {
+        gamestate.board.legal_moves().choose(&mut self.rng).copied()
+    }
+
+    fn name(&self) -> &'static str {
+        "random"
+    }
+}
+
+/// Plays the move with the best immediate outcome: ambers first, then captures. Ties are
+/// broken at random.
+#[derive(Debug, Clone)]
+pub struct GreedyStrategy {
+    rng: StdRng,
+}
+
+impl GreedyStrategy {
+    pub fn new() -> Self {
+        GreedyStrategy {
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Strategy for GreedyStrategy {
+    fn choose_move(&mut self, gamestate: &Gamestate, _: Team, _: Duration) -> Option<Move> {
+        let mut moves = gamestate.board.legal_moves();
+        //Shuffling first makes the stable maximum a random one among equals
+        moves.shuffle(&mut self.rng);
+
+        moves.iter().copied().max_by_key(|r#move| {
+            let mut next = *gamestate;
+            let ambers = next.apply(r#move);
+            (ambers, r#move.is_capture())
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+}
+
+impl Strategy for Searcher {
+    fn choose_move(&mut self, gamestate: &Gamestate, _: Team, budget: Duration) -> Option<Move> {
+        let info = self.search(gamestate, budget);
+        log::info!(
+            "Searched to depth {} with score {} ({} nodes)",
+            info.depth,
+            info.score,
+            info.nodes
+        );
+        info.best_move
+    }
+
+    fn ponder(&mut self, gamestate: &Gamestate, stop: Arc<AtomicBool>) {
+        Searcher::ponder(self, gamestate, stop);
+    }
+
+    fn last_search(&self) -> Option<&SearchInfo> {
+        Searcher::last_search(self)
+    }
+
+    fn name(&self) -> &'static str {
+        "alphabeta"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every strategy has to come up with a legal move in a position with little time.
+    #[test]
+    fn strategies_play_legal_moves() {
+        let gamestate = Gamestate::random_start(&mut StdRng::seed_from_u64(17));
+
+        let kinds = [
+            StrategyKind::Random,
+            StrategyKind::Greedy,
+            StrategyKind::AlphaBeta,
+            StrategyKind::Mcts,
+        ];
+        for kind in kinds.iter() {
+            let options = StrategyOptions {
+                tt_bits: 12,
+                ..StrategyOptions::default()
+            };
+            let mut strategy = kind.build(&options);
+            assert_eq!(strategy.name(), kind.to_string());
+
+            let r#move = strategy
+                .choose_move(
+                    &gamestate,
+                    gamestate.current_team(),
+                    Duration::from_millis(20),
+                )
+                .unwrap();
+            assert!(gamestate.board.legal_moves().contains(&r#move), "{}", kind);
+        }
+    }
+}
@@ -1,7 +1,7 @@
+use crate::error::GameError;
 use std::str::FromStr;
-use std::string::ParseError;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Team {
     ONE,
     TWO,
@@ -15,8 +15,9 @@ impl Team {
         }
     }
 
+    /// The team whose turn it is. The starting team moves on even turns.
     pub fn current(start_team: Team, turn: u8) -> Team {
-        match turn + 1 & 0x1 {
+        match turn & 0x1 {
             0 => start_team,
             1 => start_team.next(),
             _ => {
@@ -24,24 +25,24 @@ impl Team {
             }
         }
     }
+
+    /// Index of the team into per-team tables.
+    pub const fn index(&self) -> usize {
+        match self {
+            Team::ONE => 0,
+            Team::TWO => 1,
+        }
+    }
 }
 
 impl FromStr for Team {
-    type Err = ParseError;
+    type Err = GameError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "ONE" => Ok(Team::ONE),
             "TWO" => Ok(Team::TWO),
-            s => {
-                panic!("No team with name {}", s)
-            }
+            s => Err(GameError::UnknownTeam(s.to_string())),
         }
     }
 }
-
-impl From<&String> for Team {
-    fn from(team: &String) -> Self {
-        team.as_str().parse::<Team>().unwrap()
-    }
-}
@@ -0,0 +1,122 @@
+use crate::gamestate::{Gamestate, MAX_TURNS};
+use std::time::{Duration, Instant};
+
+/// Time the server allows for a move in the contest. Answering later is a soft timeout,
+/// which loses the game.
+pub const MOVE_LIMIT: Duration = Duration::from_secs(2);
+
+/// Kept back from the limit for sending the move and the latency of the network.
+pub const NETWORK_MARGIN: Duration = Duration::from_millis(300);
+
+/// Knows the per-move limit of the server and how much of it a search may use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeManager {
+    /// Time the server allows for a move
+    pub limit: Duration,
+    /// Time kept back for the network
+    pub margin: Duration,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        TimeManager {
+            limit: MOVE_LIMIT,
+            margin: NETWORK_MARGIN,
+        }
+    }
+}
+
+impl TimeManager {
+    /// The most time a search may take, measured from the move request.
+    pub fn budget(&self) -> Duration {
+        self.limit.saturating_sub(self.margin)
+    }
+}
+
+/// Time allocated to the search of one position.
+///
+/// A new iteration is only started before the target time, which depends on the game phase
+/// and on how long the best move has stayed the same. Once the maximum is reached the search
+/// is stopped, even in the middle of an iteration.
+#[derive(Copy, Clone, Debug)]
+pub struct Allocation {
+    start: Instant,
+    target: Duration,
+    maximum: Duration,
+}
+
+impl Allocation {
+    /// Allocates time for the position, starting now and ending after `budget` at the latest.
+    pub fn new(gamestate: &Gamestate, budget: Duration) -> Self {
+        Allocation {
+            start: Instant::now(),
+            target: budget.mul_f64(phase_factor(gamestate.round)),
+            maximum: budget,
+        }
+    }
+
+    /// The time at which the search has to stop.
+    pub fn deadline(&self) -> Instant {
+        self.start + self.maximum
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// The target time for the search, given in how many iterations in a row the best move
+    /// stayed the same.
+    pub fn target(&self, stable_iterations: u8) -> Duration {
+        self.target
+            .mul_f64(stability_factor(stable_iterations))
+            .min(self.maximum)
+    }
+
+    /// Whether another iteration should be started.
+    pub fn next_iteration(&self, stable_iterations: u8) -> bool {
+        self.elapsed() < self.target(stable_iterations)
+    }
+}
+
+/// Share of the budget targeted in each phase. The first moves of a game rarely decide it,
+/// while in the middle game most pieces are in contact. Towards the end fewer turns are left
+/// to search, so less time is needed to see the outcome.
+fn phase_factor(turn: u8) -> f64 {
+    match turn {
+        turn if turn < 8 => 0.35,
+        turn if turn < MAX_TURNS - 16 => 0.6,
+        _ => 0.45,
+    }
+}
+
+/// An unstable best move gets more time, one that has held for several iterations less.
+fn stability_factor(stable_iterations: u8) -> f64 {
+    match stable_iterations {
+        0 => 1.6,
+        1 => 1.0,
+        2 => 0.8,
+        _ => 0.6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_by_phase_and_stability() {
+        let budget = TimeManager::default().budget();
+        assert_eq!(budget, Duration::from_millis(1700));
+
+        let mut gamestate = Gamestate::new();
+        let opening = Allocation::new(&gamestate, budget);
+        gamestate.round = 30;
+        let middle = Allocation::new(&gamestate, budget);
+        assert!(opening.target(1) < middle.target(1));
+
+        assert!(middle.target(0) > middle.target(1));
+        assert!(middle.target(1) > middle.target(5));
+        assert!(middle.target(0) <= budget);
+        assert!(middle.deadline() <= Instant::now() + budget);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::game_move::Move;
+use std::fmt;
+use std::fmt::{Debug, Formatter};
+
+/// How the stored score relates to the true score of the position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact
+    Exact,
+    /// The search failed high, the true score is at least the stored one
+    Lower,
+    /// The search failed low, the true score is at most the stored one
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Entry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: f32,
+    pub depth: u8,
+    pub bound: Bound,
+    /// The search the entry was written in. Entries of older searches are always replaced.
+    generation: u8,
+}
+
+/// Fixed-size hash table of search results, indexed by Zobrist hash.
+///
+/// Each slot holds a single entry. A slot is overwritten if it is empty, belongs to an older
+/// search, holds the same position, or was searched less deep than the new entry.
+#[derive(Clone)]
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Creates a table with `2^bits` slots.
+    pub fn new(bits: u8) -> Self {
+        TranspositionTable {
+            entries: vec![None; 1 << bits],
+            generation: 0,
+        }
+    }
+
+    /// Marks the start of a new search, making all current entries replaceable.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// The current search, counting up with every [`TranspositionTable::new_search`].
+    pub fn generation(&self) -> u8 {
+        self.generation
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    /// Returns the entry stored for the position, if any.
+    pub fn probe(&self, key: u64) -> Option<&Entry> {
+        self.entries[self.index(key)]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+    }
+
+    /// Stores a search result, following the depth-preferred replacement scheme.
+    pub fn store(
+        &mut self,
+        key: u64,
+        best_move: Option<Move>,
+        score: f32,
+        depth: u8,
+        bound: Bound,
+    ) {
+        let generation = self.generation;
+        let index = self.index(key);
+        let slot = &mut self.entries[index];
+
+        let replace = match slot {
+            None => true,
+            Some(entry) => {
+                entry.generation != generation || entry.key == key || depth >= entry.depth
+            }
+        };
+
+        if replace {
+            //Keep the old best move if the new search didn't produce one
+            let best_move = best_move.or_else(|| {
+                slot.as_ref()
+                    .filter(|entry| entry.key == key)
+                    .and_then(|entry| entry.best_move)
+            });
+
+            *slot = Some(Entry {
+                key,
+                best_move,
+                score,
+                depth,
+                bound,
+                generation,
+            });
+        }
+    }
+}
+
+impl Debug for TranspositionTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TranspositionTable {{ slots: {}, generation: {} }}",
+            self.entries.len(),
+            self.generation
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceType;
+
+    const KEY: u64 = 0xABCD_0005;
+    /// Lands in the same slot as `KEY` in a table of 2^4 slots
+    const COLLIDING: u64 = KEY + (1 << 4);
+
+    fn some_move() -> Option<Move> {
+        Some(Move::new(3, 11, PieceType::MOEWE, false))
+    }
+
+    #[test]
+    fn stores_and_probes() {
+        let mut table = TranspositionTable::new(4);
+        assert!(table.probe(KEY).is_none());
+
+        for bound in [Bound::Exact, Bound::Lower, Bound::Upper] {
+            table.store(KEY, some_move(), -0.5, 3, bound);
+            let entry = table.probe(KEY).unwrap();
+            assert_eq!(entry.bound, bound);
+            assert_eq!(entry.score, -0.5);
+            assert_eq!(entry.depth, 3);
+            assert_eq!(entry.best_move, some_move());
+        }
+        assert!(table.probe(COLLIDING).is_none());
+
+        table.clear();
+        assert!(table.probe(KEY).is_none());
+    }
+
+    #[test]
+    fn prefers_deeper_entries() {
+        let mut table = TranspositionTable::new(4);
+        table.store(KEY, some_move(), 1.0, 5, Bound::Exact);
+
+        //A shallower result of another position doesn't replace the deeper one
+        table.store(COLLIDING, None, 2.0, 4, Bound::Lower);
+        assert_eq!(table.probe(KEY).unwrap().depth, 5);
+        assert!(table.probe(COLLIDING).is_none());
+
+        //The same position is always updated, keeping its best move
+        table.store(KEY, None, 0.5, 2, Bound::Upper);
+        let entry = table.probe(KEY).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound),
+            (2, 0.5, Bound::Upper)
+        );
+        assert_eq!(entry.best_move, some_move());
+
+        table.store(COLLIDING, None, 2.0, 2, Bound::Lower);
+        assert!(table.probe(KEY).is_none());
+        assert_eq!(table.probe(COLLIDING).unwrap().score, 2.0);
+    }
+
+    #[test]
+    fn replaces_entries_of_older_searches() {
+        let mut table = TranspositionTable::new(4);
+        table.store(KEY, some_move(), 1.0, 9, Bound::Exact);
+
+        table.new_search();
+        table.store(COLLIDING, None, 2.0, 1, Bound::Exact);
+        assert!(table.probe(KEY).is_none());
+        assert_eq!(table.probe(COLLIDING).unwrap().depth, 1);
+
+        //Within the new search the depth counts again
+        table.store(KEY, None, 1.0, 0, Bound::Exact);
+        assert!(table.probe(KEY).is_none());
+    }
+}
@@ -0,0 +1,401 @@
+use crate::cli;
+use crate::cli::Flags;
+use crate::eval;
+use crate::eval::{EvalParams, PARAM_COUNT, PARAM_NAMES};
+use crate::gamestate::Gamestate;
+use crate::match_runner;
+use crate::match_runner::Engine;
+use crate::notation;
+use crate::team::Team;
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Index of the amber weight, which is kept fixed as the unit of the evaluation.
+const AMBER: usize = 0;
+
+/// Decay rates of the moment estimates of the Adam optimizer.
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+
+/// A position of a finished game, reduced to its evaluation features, with the result of the
+/// game for team one: 1 for a win, 0.5 for a draw and 0 for a loss.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub features: [f32; PARAM_COUNT],
+    pub result: f32,
+}
+
+impl Sample {
+    pub fn new(gamestate: &Gamestate, result: f32) -> Self {
+        Sample {
+            features: eval::features(gamestate),
+            result,
+        }
+    }
+}
+
+/// The result of a game for team one.
+pub fn result_of(winner: Option<Team>) -> f32 {
+    match winner {
+        Some(Team::ONE) => 1.0,
+        Some(Team::TWO) => 0.0,
+        None => 0.5,
+    }
+}
+
+/// Writes a line of a dataset: the position in [`notation`], a `;` and the result for team
+/// one.
+pub fn write_sample(gamestate: &Gamestate, result: f32) -> String {
+    format!("{}; {}", notation::write(gamestate), result)
+}
+
+/// Parses a line written by [`write_sample`].
+pub fn parse_sample(line: &str) -> Result<Sample, String> {
+    let (position, result) = line
+        .split_once(';')
+        .ok_or_else(|| format!("Expected '<position>; <result>', got '{}'", line))?;
+    let gamestate = notation::parse(position.trim())?;
+    let result = match result.trim().parse::<f32>() {
+        Ok(result) if (0.0..=1.0).contains(&result) => result,
+        _ => return Err(format!("Invalid result '{}'", result.trim())),
+    };
+    Ok(Sample::new(&gamestate, result))
+}
+
+/// Reads a dataset with one sample per line. Empty lines and lines starting with `#` are
+/// skipped.
+pub fn load_dataset(path: &str) -> Result<Vec<Sample>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path, err))?;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            parse_sample(line).map_err(|err| format!("{}:{}: {}", path, number + 1, err))
+        })
+        .collect()
+}
+
+/// Whether the position is worth learning from. Positions in which the side to move can earn
+/// an amber right away are decided by the search, not the evaluation.
+pub fn is_quiet(gamestate: &Gamestate) -> bool {
+    !gamestate.is_over()
+        && gamestate
+            .board
+            .legal_moves()
+            .iter()
+            .all(|r#move| gamestate.board.ambers_of(r#move) == 0)
+}
+
+/// The expected result for team one of a position with the evaluation.
+fn sigmoid(eval: f64, k: f64) -> f64 {
+    1.0 / (1.0 + (-k * eval).exp())
+}
+
+fn evaluate(sample: &Sample, weights: &[f64; PARAM_COUNT]) -> f64 {
+    sample
+        .features
+        .iter()
+        .zip(weights.iter())
+        .map(|(&feature, weight)| feature as f64 * weight)
+        .sum()
+}
+
+fn weights_of(params: &EvalParams) -> [f64; PARAM_COUNT] {
+    let mut weights = [0.0; PARAM_COUNT];
+    for (weight, &value) in weights.iter_mut().zip(params.values().iter()) {
+        *weight = value as f64;
+    }
+    weights
+}
+
+/// The mean logistic loss of predicting the results of the samples with the weights.
+pub fn loss(samples: &[Sample], params: &EvalParams, k: f64) -> f64 {
+    let weights = weights_of(params);
+    mean_loss(samples, &weights, k)
+}
+
+fn mean_loss(samples: &[Sample], weights: &[f64; PARAM_COUNT], k: f64) -> f64 {
+    //Keeps the logarithms finite for predictions of exactly 0 or 1
+    let epsilon = 1e-12;
+    let total: f64 = samples
+        .iter()
+        .map(|sample| {
+            let predicted = sigmoid(evaluate(sample, weights), k).clamp(epsilon, 1.0 - epsilon);
+            let result = sample.result as f64;
+            -(result * predicted.ln() + (1.0 - result) * (1.0 - predicted).ln())
+        })
+        .sum();
+    total / samples.len().max(1) as f64
+}
+
+/// The scaling constant that turns the evaluation of the weights into the best prediction of
+/// the results, found by a golden-section search.
+pub fn fit_scale(samples: &[Sample], params: &EvalParams) -> f64 {
+    let weights = weights_of(params);
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = (0.01, 50.0);
+
+    for _ in 0..60 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if mean_loss(samples, &weights, a) < mean_loss(samples, &weights, b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Fits the weights to the samples by minimizing the logistic loss with the Adam optimizer,
+/// starting from `params`. The amber weight stays fixed, so that the evaluation keeps
+/// measuring in ambers.
+///
+/// `report` is called after every epoch with its number and the loss.
+pub fn tune<F>(
+    samples: &[Sample],
+    params: &EvalParams,
+    k: f64,
+    epochs: u32,
+    rate: f64,
+    mut report: F,
+) -> EvalParams
+where
+    F: FnMut(u32, f64),
+{
+    let mut weights = weights_of(params);
+    let mut first_moment = [0.0; PARAM_COUNT];
+    let mut second_moment = [0.0; PARAM_COUNT];
+    let count = samples.len().max(1) as f64;
+
+    for epoch in 1..=epochs {
+        let mut gradient = [0.0; PARAM_COUNT];
+        for sample in samples {
+            let error = sigmoid(evaluate(sample, &weights), k) - sample.result as f64;
+            for (gradient, &feature) in gradient.iter_mut().zip(sample.features.iter()) {
+                *gradient += error * k * feature as f64 / count;
+            }
+        }
+
+        for i in (0..PARAM_COUNT).filter(|&i| i != AMBER) {
+            first_moment[i] = BETA1 * first_moment[i] + (1.0 - BETA1) * gradient[i];
+            second_moment[i] = BETA2 * second_moment[i] + (1.0 - BETA2) * gradient[i].powi(2);
+            let first = first_moment[i] / (1.0 - BETA1.powi(epoch as i32));
+            let second = second_moment[i] / (1.0 - BETA2.powi(epoch as i32));
+            weights[i] -= rate * first / (second.sqrt() + 1e-8);
+        }
+
+        report(epoch, mean_loss(samples, &weights, k));
+    }
+
+    let mut tuned = *params;
+    for (value, weight) in tuned.values_mut().iter_mut().zip(weights.iter()) {
+        **value = *weight as f32;
+    }
+    tuned
+}
+
+/// Entry point of the `tune` command.
+///
+/// `tune generate <file>` plays games of the engine against itself and writes the quiet
+/// positions with the results to the dataset. `tune <dataset>` fits the evaluation weights to
+/// a dataset and writes them as a parameter file.
+pub fn run(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("generate") => generate(&args[1..]),
+        Some(_) => fit(args),
+        None => println!("{}\n{}", GENERATE_USAGE, FIT_USAGE),
+    }
+}
+
+const GENERATE_USAGE: &str =
+    "Usage: tune generate <file> [--games <n>] [--engine <engine>] [--seed <n>]";
+const FIT_USAGE: &str = "Usage: tune <dataset> [--output <file>] [--epochs <n>] [--rate <r>] \
+                         [--params <file>]";
+
+fn generate(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => cli::exit_with_usage("Missing the dataset file", GENERATE_USAGE),
+    };
+
+    let mut games = 100usize;
+    let mut engine = Engine::parse("name=selfplay,time=20").unwrap();
+    let mut seed = 0u64;
+
+    let mut flags = Flags::new(&args[1..]);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--games" => flags.parse().map(|value| games = value),
+            "--engine" => flags
+                .value()
+                .and_then(Engine::parse)
+                .map(|value| engine = value),
+            "--seed" => flags.parse().map(|value| seed = value),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, GENERATE_USAGE);
+        }
+    }
+
+    let mut writer = match File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => {
+            log::error!("Failed to create {}: {}", path, err);
+            return;
+        }
+    };
+
+    let mut samples = 0;
+    for game in 0..games {
+        let positions =
+            match_runner::play_game([&engine, &engine], match_runner::opening(seed, game));
+        let result = result_of(positions[positions.len() - 1].winner());
+
+        for position in positions.iter().filter(|position| is_quiet(position)) {
+            if let Err(err) = writeln!(writer, "{}", write_sample(position, result)) {
+                log::error!("Failed to write {}: {}", path, err);
+                return;
+            }
+            samples += 1;
+        }
+        println!(
+            "Game {:>4}: result {} | {} samples",
+            game + 1,
+            result,
+            samples
+        );
+    }
+
+    match writer.flush() {
+        Ok(()) => println!("Wrote {} samples to {}", samples, path),
+        Err(err) => log::error!("Failed to write {}: {}", path, err),
+    }
+}
+
+fn fit(args: &[String]) {
+    let samples = match load_dataset(&args[0]) {
+        Ok(samples) if !samples.is_empty() => samples,
+        Ok(_) => {
+            println!("The dataset {} is empty", args[0]);
+            return;
+        }
+        Err(err) => {
+            println!("{}", err);
+            return;
+        }
+    };
+
+    let mut output = String::from("eval.params");
+    let mut epochs = 1000u32;
+    let mut rate = 0.005f64;
+    let mut params = EvalParams::default();
+
+    let mut flags = Flags::new(&args[1..]);
+    while let Some(flag) = flags.next_flag() {
+        let parsed = match flag {
+            "--output" => flags.value().map(|value| output = value.to_string()),
+            "--epochs" => flags.parse().map(|value| epochs = value),
+            "--rate" => flags.parse().map(|value| rate = value),
+            "--params" => flags
+                .value()
+                .and_then(EvalParams::load)
+                .map(|value| params = value),
+            flag => Err(format!("Unknown option '{}'", flag)),
+        };
+        if let Err(err) = parsed {
+            cli::exit_with_usage(&err, FIT_USAGE);
+        }
+    }
+
+    let k = fit_scale(&samples, &params);
+    println!(
+        "{} samples | K {:.3} | Initial loss {:.5}",
+        samples.len(),
+        k,
+        loss(&samples, &params, k)
+    );
+
+    let tuned = tune(&samples, &params, k, epochs, rate, |epoch, loss| {
+        if epoch.is_multiple_of(100) || epoch == epochs {
+            println!("Epoch {:>5}: loss {:.5}", epoch, loss);
+        }
+    });
+
+    for (name, (before, after)) in PARAM_NAMES
+        .iter()
+        .zip(params.values().iter().zip(tuned.values().iter()))
+    {
+        println!("{:<16} {:>9.4} -> {:>9.4}", name, before, after);
+    }
+
+    let file = format!(
+        "# Tuned on {} samples of {} with K = {:.3}\n{}",
+        samples.len(),
+        args[0],
+        k,
+        tuned
+    );
+    match fs::write(&output, file) {
+        Ok(()) => println!("Wrote the weights to {}", output),
+        Err(err) => log::error!("Failed to write {}: {}", output, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::match_runner::random_opening;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn sample_round_trip() {
+        let gamestate = random_opening(&mut StdRng::seed_from_u64(22), 6);
+        let line = write_sample(&gamestate, 0.5);
+        assert_eq!(parse_sample(&line), Ok(Sample::new(&gamestate, 0.5)));
+
+        assert!(parse_sample(&notation::write(&gamestate)).is_err());
+        assert!(parse_sample(&format!("{}; 2", notation::write(&gamestate))).is_err());
+    }
+
+    /// Results decided by mobility alone have to teach a positive mobility weight.
+    #[test]
+    fn tuning_fits_the_results() {
+        let mut rng = StdRng::seed_from_u64(23);
+        let mobility = PARAM_NAMES
+            .iter()
+            .position(|&name| name == "mobility")
+            .unwrap();
+        let samples: Vec<Sample> = (0..300)
+            .map(|_| {
+                let gamestate = random_opening(&mut rng, 12);
+                let difference = eval::features(&gamestate)[mobility];
+                let result = if difference > 0.0 {
+                    1.0
+                } else if difference < 0.0 {
+                    0.0
+                } else {
+                    0.5
+                };
+                Sample::new(&gamestate, result)
+            })
+            .collect();
+
+        let params = EvalParams {
+            mobility: 0.0,
+            ..EvalParams::default()
+        };
+        let k = fit_scale(&samples, &params);
+        let before = loss(&samples, &params, k);
+        let tuned = tune(&samples, &params, k, 200, 0.01, |_, _| {});
+
+        assert!(loss(&samples, &tuned, k) < before);
+        assert!(tuned.mobility > 0.0);
+        assert_eq!(tuned.amber, params.amber);
+    }
+}
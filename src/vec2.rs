@@ -4,7 +4,7 @@
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Vec2 {
     pub x: i8,
     pub y: i8,
@@ -26,16 +26,12 @@ impl Vec2 {
 
     pub fn rotate_clock_90(&mut self) {
         self.y = -self.y;
-        self.x ^= self.y;
-        self.y ^= self.x;
-        self.x ^= self.y;
+        std::mem::swap(&mut self.x, &mut self.y);
     }
 
     pub fn rotate_anti_90(&mut self) {
         self.x = -self.x;
-        self.x ^= self.y;
-        self.y ^= self.x;
-        self.x ^= self.y;
+        std::mem::swap(&mut self.x, &mut self.y);
     }
 }
 
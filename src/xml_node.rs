@@ -1,6 +1,9 @@
+use crate::error::GameError;
 use std::collections::{HashMap, VecDeque};
-use std::io::BufReader;
-use std::net::TcpStream;
+use std::io::{BufReader, Read};
+use std::str::FromStr;
+use xml::attribute::OwnedAttribute;
+use xml::name::OwnedName;
 use xml::reader::XmlEvent;
 use xml::EventReader;
 
@@ -23,15 +26,94 @@ impl XmlNode {
     }
 
     pub fn child(&self, name: &str) -> Option<&XmlNode> {
-        for child in self.children.iter() {
-            if child.name.as_str() == name {
-                return Some(child);
+        self.children
+            .iter()
+            .find(|child| child.name.as_str() == name)
+    }
+
+    /// Like [`XmlNode::child`], but treats a missing child as an error.
+    pub fn required_child(&self, name: &str) -> Result<&XmlNode, GameError> {
+        self.child(name).ok_or_else(|| GameError::MissingChild {
+            node: self.name.clone(),
+            child: name.to_string(),
+        })
+    }
+
+    /// Returns the first value of the attribute with the given name.
+    pub fn attribute(&self, name: &str) -> Result<&str, GameError> {
+        self.attributes
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+            .ok_or_else(|| GameError::MissingAttribute {
+                node: self.name.clone(),
+                attribute: name.to_string(),
+            })
+    }
+
+    /// Parses the first value of the attribute with the given name.
+    pub fn parse_attribute<T: FromStr>(&self, name: &str) -> Result<T, GameError> {
+        let value = self.attribute(name)?;
+        value.parse::<T>().map_err(|_| GameError::InvalidValue {
+            node: self.name.clone(),
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    /// Creates an empty node for an element.
+    fn element(name: OwnedName, attributes: Vec<OwnedAttribute>) -> Self {
+        let mut node = XmlNode::new();
+        node.name = name.local_name;
+        for attribute in attributes {
+            node.attributes
+                .entry(attribute.name.local_name)
+                .or_default()
+                .push(attribute.value);
+        }
+        node
+    }
+
+    /// Reads a whole document, returning its root element with all descendants.
+    ///
+    /// Fails if the XML is malformed or the document ends early.
+    pub fn read_document<R: Read>(source: R) -> Result<Self, GameError> {
+        let mut parser = EventReader::new(BufReader::new(source));
+        let mut stack: Vec<XmlNode> = Vec::new();
+
+        loop {
+            match parser.next()? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => stack.push(XmlNode::element(name, attributes)),
+                XmlEvent::EndElement { .. } => {
+                    let node = stack.pop().ok_or_else(empty_stack)?;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(node),
+                        None => return Ok(node),
+                    }
+                }
+                XmlEvent::Characters(content) => {
+                    if let Some(node) = stack.last_mut() {
+                        node.data += content.as_str();
+                    }
+                }
+                XmlEvent::EndDocument => return Err(GameError::UnexpectedEof),
+                _ => {}
             }
         }
-        None
     }
 
-    pub fn read_from(xml_parser: &mut EventReader<BufReader<&TcpStream>>) -> Self {
+    /// Reads the next complete node from the parser, which can read from any source: a
+    /// connection, a file or a byte slice.
+    ///
+    /// Elements nested two levels deep within the call are returned, so the children of the
+    /// `<protocol>` root come out of the first call, and the `<data>` of a `<room>` message
+    /// out of the later ones. The parser has to be kept for the whole stream, since the whole
+    /// stream is a single XML document.
+    ///
+    /// Fails if the XML is malformed or the stream ends before the node is complete.
+    pub fn read_from<R: Read>(xml_parser: &mut EventReader<R>) -> Result<Self, GameError> {
         let mut node_stack: VecDeque<XmlNode> = VecDeque::new();
         let mut has_received_first = false;
         let mut final_node: Option<XmlNode> = None;
@@ -41,45 +123,93 @@ impl XmlNode {
                 Ok(XmlEvent::StartElement {
                     name, attributes, ..
                 }) => {
-                    let mut node = XmlNode::new();
-                    node.name = name.local_name;
-                    for attribute in attributes {
-                        let attrib_name = attribute.name.local_name;
-                        if !node.attributes.contains_key(&attrib_name) {
-                            node.attributes.insert(attrib_name.to_string(), Vec::new());
-                        }
-                        node.attributes
-                            .get_mut(&attrib_name)
-                            .unwrap()
-                            .push(attribute.value.to_string());
-                    }
+                    let node = XmlNode::element(name, attributes);
                     node_stack.push_back(node);
                     has_received_first = true;
                 }
                 Ok(XmlEvent::EndElement { .. }) => {
                     if node_stack.len() > 2 {
-                        let child = node_stack.pop_back().expect("Unexpectedly found empty XML node stack while trying to pop off new child element");
-                        let mut node = node_stack.pop_back().expect("Unexpectedly found empty XML node stack while trying to hook up new child element");
+                        let child = node_stack.pop_back().ok_or_else(empty_stack)?;
+                        let mut node = node_stack.pop_back().ok_or_else(empty_stack)?;
                         node.children.push(child);
                         node_stack.push_back(node);
                     } else if has_received_first {
-                        final_node = Some(node_stack.pop_back().expect(
-                            "Unexpectedly found empty XML node stack while trying to return node",
-                        ));
+                        final_node = Some(node_stack.pop_back().ok_or_else(empty_stack)?);
                     }
                 }
                 Ok(XmlEvent::Characters(content)) => {
-                    node_stack.back_mut().expect("Unexpectedly found empty XML node stack while trying to add characters").data += content.as_str();
+                    node_stack.back_mut().ok_or_else(empty_stack)?.data += content.as_str();
+                }
+                Ok(XmlEvent::EndDocument) => {
+                    return Err(GameError::UnexpectedEof);
                 }
-                Err(_) => {
-                    break;
+                Err(err) => {
+                    return Err(err.into());
                 }
                 _ => {}
             }
-            if final_node.is_some() {
-                break;
+            if let Some(node) = final_node {
+                return Ok(node);
             }
         }
-        final_node.unwrap()
+    }
+}
+
+fn empty_stack() -> GameError {
+    GameError::Protocol(String::from("Unexpectedly found empty XML node stack"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(xml: &str) -> EventReader<&[u8]> {
+        EventReader::new(xml.as_bytes())
+    }
+
+    #[test]
+    fn reads_messages_from_a_byte_slice() {
+        let mut parser = reader(
+            "<protocol>\n<joined roomId=\"abc\"/>\
+             <room roomId=\"abc\"><data class=\"welcomeMessage\" color=\"ONE\"/></room>\
+             <room roomId=\"abc\"><data class=\"memento\"><state turn=\"3\">\
+             <startTeam>ONE</startTeam></state></data></room>",
+        );
+
+        let joined = XmlNode::read_from(&mut parser).unwrap();
+        assert_eq!(joined.name, "joined");
+        assert_eq!(joined.attribute("roomId").unwrap(), "abc");
+
+        let welcome = XmlNode::read_from(&mut parser).unwrap();
+        assert_eq!(welcome.name, "data");
+        assert_eq!(welcome.attribute("color").unwrap(), "ONE");
+
+        let memento = XmlNode::read_from(&mut parser).unwrap();
+        let state = memento.required_child("state").unwrap();
+        assert_eq!(state.parse_attribute::<u8>("turn").unwrap(), 3);
+        assert_eq!(state.required_child("startTeam").unwrap().data, "ONE");
+        assert!(state.required_child("board").is_err());
+    }
+
+    #[test]
+    fn fails_at_the_end_of_the_stream() {
+        let mut parser = reader("<protocol><joined roomId=\"abc\"/></protocol>");
+        XmlNode::read_from(&mut parser).unwrap();
+        assert!(matches!(
+            XmlNode::read_from(&mut parser),
+            Err(GameError::UnexpectedEof)
+        ));
+
+        let mut parser = reader("<protocol><room roomId=\"abc\"><data class=");
+        assert!(XmlNode::read_from(&mut parser).is_err());
+    }
+
+    #[test]
+    fn fails_on_malformed_xml() {
+        let mut parser = reader("<protocol><joined roomId=\"abc\"></left>");
+        assert!(matches!(
+            XmlNode::read_from(&mut parser),
+            Err(GameError::Xml(_))
+        ));
     }
 }
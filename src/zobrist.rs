@@ -0,0 +1,120 @@
+use crate::gamestate::MAX_TURNS;
+use crate::piece::PieceType;
+use crate::team::Team;
+
+/// Highest amber count per team that gets its own key. Games end long before that.
+const MAX_AMBERS: usize = 15;
+
+/// Random keys for Zobrist hashing, generated at compile time.
+///
+/// A position's hash is the XOR of the keys of all occupied squares, the side key if team two
+/// is to move, the amber keys of both teams and the key of the turn. The turn is part of the
+/// hash because the game ends at the turn limit, so the same pieces score differently close to
+/// it.
+pub struct Zobrist {
+    /// Indexed by piece type, owner, tower status and position
+    pieces: [[[[u64; 64]; 2]; 2]; 4],
+    side: u64,
+    /// Indexed by team and amber count. No ambers hash to 0, so fresh states need no key.
+    ambers: [[u64; MAX_AMBERS + 1]; 2],
+    /// Indexed by turn. The first turn hashes to 0 like no ambers.
+    turns: [u64; MAX_TURNS as usize + 1],
+}
+
+pub const ZOBRIST: Zobrist = Zobrist::new();
+
+impl Zobrist {
+    const fn new() -> Self {
+        let mut state = 0x0DDB_1A5E_5BAD_5EED_u64;
+
+        let mut pieces = [[[[0u64; 64]; 2]; 2]; 4];
+        let mut piece = 0;
+        while piece < 4 {
+            let mut team = 0;
+            while team < 2 {
+                let mut stacked = 0;
+                while stacked < 2 {
+                    let mut pos = 0;
+                    while pos < 64 {
+                        state = splitmix64(state);
+                        pieces[piece][team][stacked][pos] = state;
+                        pos += 1;
+                    }
+                    stacked += 1;
+                }
+                team += 1;
+            }
+            piece += 1;
+        }
+
+        state = splitmix64(state);
+        let side = state;
+
+        let mut ambers = [[0u64; MAX_AMBERS + 1]; 2];
+        let mut team = 0;
+        while team < 2 {
+            let mut count = 1;
+            while count <= MAX_AMBERS {
+                state = splitmix64(state);
+                ambers[team][count] = state;
+                count += 1;
+            }
+            team += 1;
+        }
+
+        let mut turns = [0u64; MAX_TURNS as usize + 1];
+        let mut turn = 1;
+        while turn <= MAX_TURNS as usize {
+            state = splitmix64(state);
+            turns[turn] = state;
+            turn += 1;
+        }
+
+        Zobrist {
+            pieces,
+            side,
+            ambers,
+            turns,
+        }
+    }
+
+    /// Key of a piece of the given team standing at the position.
+    pub const fn piece(&self, piece: PieceType, team: Team, stacked: bool, pos: u8) -> u64 {
+        let piece = match piece {
+            PieceType::ROBBE => 0,
+            PieceType::MUSCHEL => 1,
+            PieceType::SEESTERN => 2,
+            PieceType::MOEWE => 3,
+        };
+        self.pieces[piece][team.index()][stacked as usize][pos as usize]
+    }
+
+    /// Key toggled whenever the turn passes on.
+    pub const fn side(&self) -> u64 {
+        self.side
+    }
+
+    /// Key of the team holding the given number of ambers.
+    pub const fn ambers(&self, team: Team, count: u8) -> u64 {
+        let count = if count as usize > MAX_AMBERS {
+            MAX_AMBERS
+        } else {
+            count as usize
+        };
+        self.ambers[team.index()][count]
+    }
+
+    /// Key of the given turn. Turns after the limit share the key of the last one.
+    pub const fn turn(&self, turn: u8) -> u64 {
+        let turn = if turn > MAX_TURNS { MAX_TURNS } else { turn };
+        self.turns[turn as usize]
+    }
+}
+
+/// Step of the SplitMix64 generator, returning the next state which doubles as the output.
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}